@@ -0,0 +1,40 @@
+#!/bin/sh
+# Builds the portable SPIR-V modules that registry::build_program loads when
+# no cached vendor binary matches, needs clang with the spir64 target and
+# llvm-spirv on the PATH.
+#
+# The modules are not checked in and cargo does not build them. Run this
+# script once after changing src/opencl/vecadd/vecadd.cl; without the
+# modules build_program falls back to compiling the source. They are
+# written to $CLVECADD_CACHE_DIR/spirv, or src/bin/vecadd/spirv when the
+# variable is unset, which is where build_program looks for them.
+set -e
+
+# FNV-1a of a file as lowercase hex, the same hash as options::get_stable_hash;
+# build_program skips a module whose recorded hash no longer matches vecadd.cl
+source_hash() {
+  od -An -v -tu1 "$1" | tr -s ' ' '\n' | grep -v '^$' | {
+    hash=-3750763034362895579
+    while read -r byte; do
+      hash=$(( (hash ^ byte) * 1099511628211 ))
+    done
+    printf '%016x\n' "$hash"
+  }
+}
+
+cd "$(dirname "$0")/.."
+out="${CLVECADD_CACHE_DIR:-src/bin/vecadd}/spirv"
+mkdir -p "$out"
+hash=$(source_hash src/opencl/vecadd/vecadd.cl)
+
+for type in char short int long uchar ushort uint ulong half float double; do
+  case "$type" in
+    half|float|double) float=-DARRAY_TYPE_IS_FLOAT ;;
+    *) float= ;;
+  esac
+  clang -c -x cl -cl-std=CL1.2 -target spir64 -O2 -emit-llvm \
+    -D ARRAY_TYPE="$type" $float -o "$out/vecadd_$type.bc" src/opencl/vecadd/vecadd.cl
+  llvm-spirv "$out/vecadd_$type.bc" -o "$out/vecadd_$type.spv"
+  rm "$out/vecadd_$type.bc"
+  echo "$hash" > "$out/vecadd_$type.spv.hash"
+done
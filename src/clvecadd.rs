@@ -1,4 +1,25 @@
 pub mod traits;
 pub mod setup;
+pub mod aot;
+pub mod arith;
 pub mod buffer;
-pub mod exec;
\ No newline at end of file
+pub mod buildlog;
+pub mod caps;
+pub mod container;
+pub mod elementwise;
+pub mod events;
+pub mod exec;
+pub mod future;
+pub mod graph;
+pub mod il;
+pub mod image;
+pub mod launch;
+pub mod link;
+pub mod mask;
+pub mod math;
+pub mod options;
+pub mod pool;
+pub mod profile;
+pub mod registry;
+pub mod tune;
+pub mod trace;
\ No newline at end of file
@@ -0,0 +1,147 @@
+use opencl3::context;
+use opencl3::device;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::clvecadd::buildlog;
+use crate::clvecadd::caps;
+use crate::clvecadd::container;
+use crate::clvecadd::options;
+use crate::clvecadd::traits;
+
+pub struct KernelSource {
+    pub name: &'static str,
+    pub path: &'static str,
+    pub type_define: &'static str,
+}
+
+pub struct ExcludedSource {
+    pub name: &'static str,
+    pub path: &'static str,
+    pub reason: &'static str,
+}
+
+// sources built per element type
+pub const KERNEL_SOURCES: [KernelSource; 1] = [KernelSource {
+    name: "vecadd",
+    path: "src/opencl/vecadd/vecadd.cl",
+    type_define: "ARRAY_TYPE",
+}];
+
+// kernels that are not built ahead of time, reported by clvecadd-build
+pub const EXCLUDED_SOURCES: [ExcludedSource; 2] = [
+    ExcludedSource {
+        name: "imageops",
+        path: "src/opencl/image/imageops.cl",
+        reason: "built per pixel format when an image operation is prepared, without the binary cache",
+    },
+    ExcludedSource {
+        name: "elementwise",
+        path: "generated",
+        reason: "generated from an ElementwiseOp at run time and cached on first use",
+    },
+];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BuildOutcome {
+    pub kernel: String,
+    pub element_type: String,
+    pub device: String,
+    pub result: Result<PathBuf, String>,
+}
+
+pub fn get_build_options(context: &context::Context, source: &KernelSource, element_type: &str) -> Result<String, String> {
+    let build_options = match options::BuildOptions::new().detect_standard(context) {
+        Ok(build_options) => build_options.define(source.type_define, element_type),
+        Err(error) => return Err(error),
+    };
+
+    // OpenCL C has no way to ask whether a type is floating point
+    if traits::is_float_type(element_type) {
+        Ok(build_options.flag(&format!("{}_IS_FLOAT", source.type_define)).to_string())
+    } else {
+        Ok(build_options.to_string())
+    }
+}
+
+fn build_one(
+    context: &context::Context,
+    source: &KernelSource,
+    content: &str,
+    element_type: &str,
+    cache_dir: &Path,
+) -> Result<PathBuf, String> {
+    match caps::check_type_name_support(context, element_type) {
+        Ok(_) => (),
+        Err(error) => return Err(error.into()),
+    };
+
+    let options = match get_build_options(context, source, element_type) {
+        Ok(options) => options,
+        Err(error) => return Err(error),
+    };
+
+    let device_id = match context.devices().first() {
+        Some(device_id) => *device_id,
+        None => return Err(String::from("context has no devices")),
+    };
+
+    let path = match container::get_binary_path(cache_dir, source.name, device_id, &options) {
+        Ok(path) => path,
+        Err(error) => return Err(error),
+    };
+
+    let (prog, _) = match buildlog::build_from_source(context, source.path, content, &options) {
+        Ok(built) => built,
+        Err(failure) => return Err(failure.into()),
+    };
+
+    match container::write_binary(&prog, &path, &options, content.as_bytes()) {
+        Ok(_) => Ok(path),
+        Err(error) => Err(error),
+    }
+}
+
+pub fn build_all(devices: &[device::Device], cache_dir: &Path) -> Result<Vec<BuildOutcome>, String> {
+    let mut sources: Vec<(&KernelSource, String)> = Vec::new();
+    for source in &KERNEL_SOURCES {
+        match fs::read_to_string(source.path) {
+            Ok(content) => sources.push((source, content)),
+            Err(error) => return Err(format!("error reading source file {}: {}", source.path, error)),
+        };
+    }
+
+    let mut outcomes = Vec::new();
+    for device in devices {
+        let device_name = match device.name() {
+            Ok(device_name) => device_name.trim().to_string(),
+            Err(error) => return Err(format!("not able to get device name: {}", error)),
+        };
+
+        let context = match context::Context::from_device(device) {
+            Ok(context) => context,
+            Err(error) => {
+                outcomes.push(BuildOutcome {
+                    kernel: String::from("*"),
+                    element_type: String::from("*"),
+                    device: device_name,
+                    result: Err(format!("error getting context: {}", error)),
+                });
+                continue;
+            }
+        };
+
+        for (source, content) in &sources {
+            for element_type in traits::get_opencl_num_types() {
+                outcomes.push(BuildOutcome {
+                    kernel: source.name.to_string(),
+                    element_type: element_type.to_string(),
+                    device: device_name.clone(),
+                    result: build_one(&context, source, content, element_type, cache_dir),
+                });
+            }
+        }
+    }
+
+    Ok(outcomes)
+}
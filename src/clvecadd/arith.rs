@@ -0,0 +1,421 @@
+use half::f16;
+use opencl3::command_queue;
+use opencl3::context;
+
+use crate::clvecadd::launch;
+use crate::clvecadd::registry;
+use crate::clvecadd::traits;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Min,
+    Max,
+}
+
+// what integer add and sub do when the result does not fit, floats always follow IEEE
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Overflow {
+    #[default]
+    Wrapping,
+    Saturating,
+    Checked,
+}
+
+pub const INTEGER_OVERFLOW: &str = "integer overflow";
+
+pub fn is_integer_overflow(error: &str) -> bool {
+    error.starts_with(INTEGER_OVERFLOW)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum UnaryOp {
+    Abs,
+    Neg,
+}
+
+impl BinaryOp {
+    pub const ALL: [BinaryOp; 7] = [
+        BinaryOp::Add,
+        BinaryOp::Sub,
+        BinaryOp::Mul,
+        BinaryOp::Div,
+        BinaryOp::Rem,
+        BinaryOp::Min,
+        BinaryOp::Max,
+    ];
+
+    pub fn kernel(&self) -> registry::Kernels {
+        match self {
+            BinaryOp::Add => registry::Kernels::AddVectors,
+            BinaryOp::Sub => registry::Kernels::SubVectors,
+            BinaryOp::Mul => registry::Kernels::MulVectors,
+            BinaryOp::Div => registry::Kernels::DivVectors,
+            BinaryOp::Rem => registry::Kernels::RemVectors,
+            BinaryOp::Min => registry::Kernels::MinVectors,
+            BinaryOp::Max => registry::Kernels::MaxVectors,
+        }
+    }
+
+    pub fn overflow_kernel(&self, mode: Overflow) -> Result<registry::Kernels, String> {
+        match (self, mode) {
+            (_, Overflow::Wrapping) => Ok(self.kernel()),
+            (BinaryOp::Add, Overflow::Saturating) => Ok(registry::Kernels::AddVectorsSat),
+            (BinaryOp::Sub, Overflow::Saturating) => Ok(registry::Kernels::SubVectorsSat),
+            (BinaryOp::Add, Overflow::Checked) => Ok(registry::Kernels::AddVectorsChecked),
+            (BinaryOp::Sub, Overflow::Checked) => Ok(registry::Kernels::SubVectorsChecked),
+            _ => Err(format!("{:?} has no {:?} variant", self, mode)),
+        }
+    }
+}
+
+impl UnaryOp {
+    pub const ALL: [UnaryOp; 2] = [UnaryOp::Abs, UnaryOp::Neg];
+
+    pub fn kernel(&self) -> registry::Kernels {
+        match self {
+            UnaryOp::Abs => registry::Kernels::AbsVector,
+            UnaryOp::Neg => registry::Kernels::NegVector,
+        }
+    }
+}
+
+// cpu reference with the semantics of vecadd.cl: integers wrap on overflow,
+// x / 0 == 0 and x % 0 == x, floats follow IEEE with fmod, fmin and fmax
+pub trait ArithNum: Copy {
+    fn apply_binary(op: BinaryOp, a: Self, b: Self) -> Self;
+    fn apply_unary(op: UnaryOp, a: Self) -> Self;
+}
+
+macro_rules! impl_arith_num_int {
+    ($t:ty, $abs:expr) => {
+        impl ArithNum for $t {
+            fn apply_binary(op: BinaryOp, a: $t, b: $t) -> $t {
+                match op {
+                    BinaryOp::Add => a.wrapping_add(b),
+                    BinaryOp::Sub => a.wrapping_sub(b),
+                    BinaryOp::Mul => a.wrapping_mul(b),
+                    BinaryOp::Div if b == 0 => 0,
+                    BinaryOp::Div => a.wrapping_div(b),
+                    BinaryOp::Rem if b == 0 => a,
+                    BinaryOp::Rem => a.wrapping_rem(b),
+                    BinaryOp::Min => std::cmp::min(a, b),
+                    BinaryOp::Max => std::cmp::max(a, b),
+                }
+            }
+
+            fn apply_unary(op: UnaryOp, a: $t) -> $t {
+                match op {
+                    UnaryOp::Abs => $abs(a),
+                    UnaryOp::Neg => a.wrapping_neg(),
+                }
+            }
+        }
+    };
+}
+
+impl_arith_num_int!(i8, i8::wrapping_abs);
+impl_arith_num_int!(i16, i16::wrapping_abs);
+impl_arith_num_int!(i32, i32::wrapping_abs);
+impl_arith_num_int!(i64, i64::wrapping_abs);
+impl_arith_num_int!(u8, |a: u8| a);
+impl_arith_num_int!(u16, |a: u16| a);
+impl_arith_num_int!(u32, |a: u32| a);
+impl_arith_num_int!(u64, |a: u64| a);
+
+pub trait IntegerNum: ArithNum {
+    fn apply_saturating(op: BinaryOp, a: Self, b: Self) -> Self;
+    fn apply_checked(op: BinaryOp, a: Self, b: Self) -> Option<Self>;
+}
+
+macro_rules! impl_integer_num {
+    ($t:ty) => {
+        impl IntegerNum for $t {
+            fn apply_saturating(op: BinaryOp, a: $t, b: $t) -> $t {
+                match op {
+                    BinaryOp::Add => a.saturating_add(b),
+                    BinaryOp::Sub => a.saturating_sub(b),
+                    _ => <$t>::apply_binary(op, a, b),
+                }
+            }
+
+            fn apply_checked(op: BinaryOp, a: $t, b: $t) -> Option<$t> {
+                match op {
+                    BinaryOp::Add => a.checked_add(b),
+                    BinaryOp::Sub => a.checked_sub(b),
+                    _ => Some(<$t>::apply_binary(op, a, b)),
+                }
+            }
+        }
+    };
+}
+
+impl_integer_num!(i8);
+impl_integer_num!(i16);
+impl_integer_num!(i32);
+impl_integer_num!(i64);
+impl_integer_num!(u8);
+impl_integer_num!(u16);
+impl_integer_num!(u32);
+impl_integer_num!(u64);
+
+macro_rules! impl_arith_num_float {
+    ($t:ty) => {
+        impl ArithNum for $t {
+            fn apply_binary(op: BinaryOp, a: $t, b: $t) -> $t {
+                match op {
+                    BinaryOp::Add => a + b,
+                    BinaryOp::Sub => a - b,
+                    BinaryOp::Mul => a * b,
+                    BinaryOp::Div => a / b,
+                    BinaryOp::Rem => a % b,
+                    BinaryOp::Min => a.min(b),
+                    BinaryOp::Max => a.max(b),
+                }
+            }
+
+            fn apply_unary(op: UnaryOp, a: $t) -> $t {
+                match op {
+                    UnaryOp::Abs => a.abs(),
+                    UnaryOp::Neg => -a,
+                }
+            }
+        }
+    };
+}
+
+impl_arith_num_float!(f32);
+impl_arith_num_float!(f64);
+
+// computed in single precision and rounded once, which is exact for these operations
+impl ArithNum for f16 {
+    fn apply_binary(op: BinaryOp, a: f16, b: f16) -> f16 {
+        f16::from_f32(<f32>::apply_binary(op, a.to_f32(), b.to_f32()))
+    }
+
+    fn apply_unary(op: UnaryOp, a: f16) -> f16 {
+        f16::from_f32(<f32>::apply_unary(op, a.to_f32()))
+    }
+}
+
+pub fn binary_op_cpu<T: ArithNum>(op: BinaryOp, a: &[T], b: &[T]) -> Result<Vec<T>, String> {
+    let size = std::cmp::min(a.len(), b.len());
+    Ok((0..size).map(|i| <T>::apply_binary(op, a[i], b[i])).collect())
+}
+
+pub fn unary_op_cpu<T: ArithNum>(op: UnaryOp, a: &[T]) -> Result<Vec<T>, String> {
+    Ok(a.iter().map(|value| <T>::apply_unary(op, *value)).collect())
+}
+
+pub fn overflow_op_cpu<T: IntegerNum>(mode: Overflow, op: BinaryOp, a: &[T], b: &[T]) -> Result<Vec<T>, String> {
+    match op.overflow_kernel(mode) {
+        Ok(_) => (),
+        Err(error) => return Err(error),
+    };
+
+    let size = std::cmp::min(a.len(), b.len());
+    match mode {
+        Overflow::Wrapping => binary_op_cpu(op, a, b),
+        Overflow::Saturating => Ok((0..size).map(|i| <T>::apply_saturating(op, a[i], b[i])).collect()),
+        Overflow::Checked => {
+            let mut c = Vec::with_capacity(size);
+            for i in 0..size {
+                match <T>::apply_checked(op, a[i], b[i]) {
+                    Some(value) => c.push(value),
+                    None => return Err(format!("{} in {:?} at element {}", INTEGER_OVERFLOW, op, i)),
+                };
+            }
+            Ok(c)
+        }
+    }
+}
+
+pub fn binary_op<T: Default + Copy + traits::OpenclNum + traits::HasOpenclString>(
+    context: &context::Context,
+    queue: &command_queue::CommandQueue,
+    op: BinaryOp,
+    a: &[T],
+    b: &[T],
+) -> Result<Vec<T>, String> {
+    let size = std::cmp::min(a.len(), b.len());
+    let mut c: Vec<T> = vec![<T>::default(); size];
+    let args = vec![
+        launch::IntoKernelArg::into_arg(&a[..size], context, queue),
+        launch::IntoKernelArg::into_arg(&b[..size], context, queue),
+        launch::IntoKernelArg::into_arg(&mut c[..], context, queue),
+        launch::IntoKernelArg::into_arg(size as u64, context, queue),
+    ];
+
+    match launch::run_kernel::<T>(context, queue, op.kernel(), args) {
+        Ok(_) => Ok(c),
+        Err(error) => Err(error),
+    }
+}
+
+pub fn unary_op<T: Default + Copy + traits::OpenclNum + traits::HasOpenclString>(
+    context: &context::Context,
+    queue: &command_queue::CommandQueue,
+    op: UnaryOp,
+    a: &[T],
+) -> Result<Vec<T>, String> {
+    let mut c: Vec<T> = vec![<T>::default(); a.len()];
+    let args = vec![
+        launch::IntoKernelArg::into_arg(a, context, queue),
+        launch::IntoKernelArg::into_arg(&mut c[..], context, queue),
+        launch::IntoKernelArg::into_arg(a.len() as u64, context, queue),
+    ];
+
+    match launch::run_kernel::<T>(context, queue, op.kernel(), args) {
+        Ok(_) => Ok(c),
+        Err(error) => Err(error),
+    }
+}
+
+pub fn scale_cpu<T: ArithNum>(alpha: T, x: &[T]) -> Result<Vec<T>, String> {
+    Ok(x.iter().map(|value| <T>::apply_binary(BinaryOp::Mul, alpha, *value)).collect())
+}
+
+pub fn add_scalar_cpu<T: ArithNum>(x: &[T], s: T) -> Result<Vec<T>, String> {
+    Ok(x.iter().map(|value| <T>::apply_binary(BinaryOp::Add, *value, s)).collect())
+}
+
+// y = alpha * x + y
+pub fn axpy_cpu<T: ArithNum>(alpha: T, x: &[T], y: &mut [T]) -> Result<(), String> {
+    for (value, x_value) in y.iter_mut().zip(x) {
+        *value = <T>::apply_binary(BinaryOp::Add, <T>::apply_binary(BinaryOp::Mul, alpha, *x_value), *value);
+    }
+    Ok(())
+}
+
+// y = alpha * x + beta * y
+pub fn axpby_cpu<T: ArithNum>(alpha: T, x: &[T], beta: T, y: &mut [T]) -> Result<(), String> {
+    for (value, x_value) in y.iter_mut().zip(x) {
+        *value = <T>::apply_binary(
+            BinaryOp::Add,
+            <T>::apply_binary(BinaryOp::Mul, alpha, *x_value),
+            <T>::apply_binary(BinaryOp::Mul, beta, *value),
+        );
+    }
+    Ok(())
+}
+
+pub fn scale<T: Default + Copy + traits::OpenclNum + traits::HasOpenclString>(
+    context: &context::Context,
+    queue: &command_queue::CommandQueue,
+    alpha: T,
+    x: &[T],
+) -> Result<Vec<T>, String> {
+    let mut c: Vec<T> = vec![<T>::default(); x.len()];
+    let args = vec![
+        launch::IntoKernelArg::into_arg(alpha, context, queue),
+        launch::IntoKernelArg::into_arg(x, context, queue),
+        launch::IntoKernelArg::into_arg(&mut c[..], context, queue),
+        launch::IntoKernelArg::into_arg(x.len() as u64, context, queue),
+    ];
+
+    match launch::run_kernel::<T>(context, queue, registry::Kernels::ScaleVector, args) {
+        Ok(_) => Ok(c),
+        Err(error) => Err(error),
+    }
+}
+
+pub fn add_scalar<T: Default + Copy + traits::OpenclNum + traits::HasOpenclString>(
+    context: &context::Context,
+    queue: &command_queue::CommandQueue,
+    x: &[T],
+    s: T,
+) -> Result<Vec<T>, String> {
+    let mut c: Vec<T> = vec![<T>::default(); x.len()];
+    let args = vec![
+        launch::IntoKernelArg::into_arg(x, context, queue),
+        launch::IntoKernelArg::into_arg(s, context, queue),
+        launch::IntoKernelArg::into_arg(&mut c[..], context, queue),
+        launch::IntoKernelArg::into_arg(x.len() as u64, context, queue),
+    ];
+
+    match launch::run_kernel::<T>(context, queue, registry::Kernels::AddScalar, args) {
+        Ok(_) => Ok(c),
+        Err(error) => Err(error),
+    }
+}
+
+// only the first min(x.len(), y.len()) elements of y are updated, like axpy_cpu
+pub fn axpy<T: Copy + traits::OpenclNum + traits::HasOpenclString>(
+    context: &context::Context,
+    queue: &command_queue::CommandQueue,
+    alpha: T,
+    x: &[T],
+    y: &mut [T],
+) -> Result<(), String> {
+    let size = std::cmp::min(x.len(), y.len());
+    let args = vec![
+        launch::IntoKernelArg::into_arg(alpha, context, queue),
+        launch::IntoKernelArg::into_arg(&x[..size], context, queue),
+        launch::IntoKernelArg::into_arg(&mut y[..size], context, queue),
+        launch::IntoKernelArg::into_arg(size as u64, context, queue),
+    ];
+
+    launch::run_kernel::<T>(context, queue, registry::Kernels::Axpy, args)
+}
+
+pub fn axpby<T: Copy + traits::OpenclNum + traits::HasOpenclString>(
+    context: &context::Context,
+    queue: &command_queue::CommandQueue,
+    alpha: T,
+    x: &[T],
+    beta: T,
+    y: &mut [T],
+) -> Result<(), String> {
+    let size = std::cmp::min(x.len(), y.len());
+    let args = vec![
+        launch::IntoKernelArg::into_arg(alpha, context, queue),
+        launch::IntoKernelArg::into_arg(&x[..size], context, queue),
+        launch::IntoKernelArg::into_arg(beta, context, queue),
+        launch::IntoKernelArg::into_arg(&mut y[..size], context, queue),
+        launch::IntoKernelArg::into_arg(size as u64, context, queue),
+    ];
+
+    launch::run_kernel::<T>(context, queue, registry::Kernels::Axpby, args)
+}
+
+// the gpu only reports that some element overflowed, overflow_op_cpu names the first one
+pub fn overflow_op<T: Default + Copy + traits::OpenclNum + traits::HasOpenclString>(
+    context: &context::Context,
+    queue: &command_queue::CommandQueue,
+    mode: Overflow,
+    op: BinaryOp,
+    a: &[T],
+    b: &[T],
+) -> Result<Vec<T>, String> {
+    let handle = match op.overflow_kernel(mode) {
+        Ok(handle) => handle,
+        Err(error) => return Err(error),
+    };
+
+    let size = std::cmp::min(a.len(), b.len());
+    let mut c: Vec<T> = vec![<T>::default(); size];
+    let mut overflow: [u32; 1] = [0];
+    let mut args = vec![
+        launch::IntoKernelArg::into_arg(&a[..size], context, queue),
+        launch::IntoKernelArg::into_arg(&b[..size], context, queue),
+        launch::IntoKernelArg::into_arg(&mut c[..], context, queue),
+    ];
+    if mode == Overflow::Checked {
+        args.push(launch::IntoKernelArg::into_arg(&mut overflow[..], context, queue));
+    }
+    args.push(launch::IntoKernelArg::into_arg(size as u64, context, queue));
+
+    match launch::run_kernel::<T>(context, queue, handle, args) {
+        Ok(_) => (),
+        Err(error) => return Err(error),
+    };
+
+    if overflow[0] != 0 {
+        return Err(format!("{} in {:?}", INTEGER_OVERFLOW, op));
+    }
+    Ok(c)
+}
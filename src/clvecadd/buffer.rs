@@ -6,24 +6,30 @@ use opencl3::memory;
 use opencl3::memory::ClMem;
 use std::ffi::c_void;
 
+use crate::clvecadd::events;
+use crate::clvecadd::trace;
+
 pub enum MemMode {
     Read,
     Write,
     ReadWrite,
 }
 
+pub fn get_mem_flag(mode: MemMode) -> cl_mem_flags {
+    match mode {
+        MemMode::Read => memory::CL_MEM_READ_ONLY,
+        MemMode::Write => memory::CL_MEM_WRITE_ONLY,
+        MemMode::ReadWrite => memory::CL_MEM_READ_WRITE,
+    }
+}
+
 pub fn create_buffer<T>(
     context: &context::Context,
     input: &mut Vec<T>,
     mode: MemMode,
 ) -> Result<memory::Buffer<T>, String> {
     let alloc_flag = memory::CL_MEM_USE_HOST_PTR;
-    let mem_flag: cl_mem_flags;
-    match mode {
-        MemMode::Read => mem_flag = memory::CL_MEM_READ_ONLY,
-        MemMode::Write => mem_flag = memory::CL_MEM_WRITE_ONLY,
-        MemMode::ReadWrite => mem_flag = memory::CL_MEM_READ_WRITE,
-    }
+    let mem_flag = get_mem_flag(mode);
 
     unsafe {
         let data = input.as_mut_ptr() as *mut c_void;
@@ -41,66 +47,70 @@ pub fn write_buffer<T: Copy>(
     queue: &command_queue::CommandQueue,
     buffer: &mut memory::Buffer<T>,
     input: &mut Vec<T>,
-    wait: &Vec<event::Event>,
+    wait: impl AsRef<[event::Event]>,
 ) -> Result<event::Event, String> {
-    unsafe {
-        let mut cl_events: Vec<opencl3::types::cl_event> = Vec::new();
-        for event in wait {
-            cl_events.push(event.get());
-        }
+    enqueue_write_buffer(queue, buffer, input, &events::EventList::from(wait.as_ref()))
+}
 
+pub fn read_buffer<T: Copy>(
+    queue: &command_queue::CommandQueue,
+    buffer: &mut memory::Buffer<T>,
+    input: &mut Vec<T>,
+    wait: impl AsRef<[event::Event]>,
+) -> Result<event::Event, String> {
+    enqueue_read_buffer(queue, buffer, input, &events::EventList::from(wait.as_ref()))
+}
+
+pub fn enqueue_write_buffer<T: Copy>(
+    queue: &command_queue::CommandQueue,
+    buffer: &memory::Buffer<T>,
+    input: &[T],
+    wait_list: &events::EventList,
+) -> Result<event::Event, String> {
+    unsafe {
         let event = match command_queue::enqueue_write_buffer(
             queue.get(),
-            buffer.get_mut(),
+            buffer.get(),
             command_queue::CL_NON_BLOCKING,
             0,
-            (input.len() * std::mem::size_of::<T>()) as usize,
+            std::mem::size_of_val(input),
             input.as_ptr() as *mut c_void,
-            cl_events.len() as u32,
-            if !cl_events.is_empty() {
-                cl_events.as_ptr()
-            } else {
-                std::ptr::null()
-            },
+            wait_list.count(),
+            wait_list.as_ptr(),
         ) {
             Ok(event) => event,
             Err(error) => return Err(format!("error writing buffer: {}", error)),
         };
 
-        Ok(event::Event::from(event))
+        let event = event::Event::from(event);
+        trace::record_transfer(queue, &event, "write_buffer", std::mem::size_of_val(input), wait_list);
+        Ok(event)
     }
 }
 
-pub fn read_buffer<T: Copy>(
+pub fn enqueue_read_buffer<T: Copy>(
     queue: &command_queue::CommandQueue,
-    buffer: &mut memory::Buffer<T>,
-    input: &mut Vec<T>,
-    wait: &Vec<event::Event>,
+    buffer: &memory::Buffer<T>,
+    output: &mut [T],
+    wait_list: &events::EventList,
 ) -> Result<event::Event, String> {
-    let mut cl_events: Vec<opencl3::types::cl_event> = Vec::new();
-    for event in wait {
-        cl_events.push(event.get());
-    }
-
     unsafe {
         let event = match command_queue::enqueue_read_buffer(
             queue.get(),
             buffer.get(),
             command_queue::CL_NON_BLOCKING,
             0,
-            (input.len() * std::mem::size_of::<T>()) as usize,
-            input.as_mut_ptr() as *mut c_void,
-            cl_events.len() as u32,
-            if !cl_events.is_empty() {
-                cl_events.as_ptr()
-            } else {
-                std::ptr::null()
-            },
+            std::mem::size_of_val(output),
+            output.as_mut_ptr() as *mut c_void,
+            wait_list.count(),
+            wait_list.as_ptr(),
         ) {
             Ok(event) => event,
             Err(error) => return Err(format!("error reading buffer: {}", error)),
         };
 
-        Ok(event::Event::from(event))
+        let event = event::Event::from(event);
+        trace::record_transfer(queue, &event, "read_buffer", std::mem::size_of_val(output), wait_list);
+        Ok(event)
     }
 }
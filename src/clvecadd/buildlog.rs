@@ -0,0 +1,216 @@
+use opencl3::context;
+use opencl3::device;
+use opencl3::program;
+use opencl3::types::{cl_device_id, cl_program};
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+    pub severity: Severity,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BuildLog {
+    pub device: String,
+    pub log: String,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BuildFailure {
+    pub source: String,
+    pub error: String,
+    pub logs: Vec<BuildLog>,
+}
+
+impl BuildLog {
+    pub fn errors(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics.iter().filter(|diagnostic| diagnostic.severity == Severity::Error)
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics.iter().filter(|diagnostic| diagnostic.severity == Severity::Warning)
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+        };
+
+        match (&self.file, self.line, self.column) {
+            (Some(file), Some(line), Some(column)) => write!(f, "{}:{}:{}: {}: {}", file, line, column, severity, self.message),
+            (Some(file), Some(line), None) => write!(f, "{}:{}: {}: {}", file, line, severity, self.message),
+            _ => write!(f, "{}: {}", severity, self.message),
+        }
+    }
+}
+
+impl fmt::Display for BuildFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "error building source file {}: {}", self.source, self.error)?;
+        for log in &self.logs {
+            // fall back to the raw log when the vendor format is not recognised
+            if log.diagnostics.is_empty() {
+                if !log.log.trim().is_empty() {
+                    write!(f, "\n{}:\n{}", log.device, log.log.trim_end())?;
+                }
+                continue;
+            }
+
+            write!(f, "\n{}:", log.device)?;
+            for diagnostic in &log.diagnostics {
+                write!(f, "\n  {}", diagnostic)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl From<BuildFailure> for String {
+    fn from(failure: BuildFailure) -> String {
+        failure.to_string()
+    }
+}
+
+const SEVERITIES: [(&str, Severity); 4] = [
+    ("fatal error: ", Severity::Error),
+    ("error: ", Severity::Error),
+    ("warning: ", Severity::Warning),
+    ("note: ", Severity::Note),
+];
+
+// clang based compilers (AMD, Intel, NVIDIA, POCL, Apple) report
+// "<file>:<line>:<column>: <severity>: <message>", source excerpts and carets are skipped
+pub fn parse_diagnostic(line: &str) -> Option<Diagnostic> {
+    let line = line.trim();
+    for (marker, severity) in SEVERITIES {
+        if let Some(message) = line.strip_prefix(marker) {
+            return Some(Diagnostic {
+                file: None,
+                line: None,
+                column: None,
+                severity,
+                message: message.trim().to_string(),
+            });
+        }
+
+        let position = match line.find(&format!(": {}", marker)) {
+            Some(position) => position,
+            None => continue,
+        };
+        let location = &line[..position];
+        let message = line[position + 2 + marker.len()..].trim().to_string();
+
+        let mut parts = location.rsplitn(3, ':');
+        let last = parts.next().and_then(|part| part.trim().parse::<u32>().ok());
+        let middle = parts.next();
+        let rest = parts.next();
+
+        let (file, line, column) = match (last, middle, rest) {
+            (Some(column), Some(middle), Some(file)) => match middle.trim().parse::<u32>() {
+                Ok(line) => (Some(file.to_string()), Some(line), Some(column)),
+                Err(_) => (Some(format!("{}:{}", file, middle)), Some(column), None),
+            },
+            (Some(line), Some(file), None) => (Some(file.to_string()), Some(line), None),
+            _ => (Some(location.to_string()), None, None),
+        };
+
+        return Some(Diagnostic {
+            file,
+            line,
+            column,
+            severity,
+            message,
+        });
+    }
+
+    None
+}
+
+pub fn parse_build_log(log: &str) -> Vec<Diagnostic> {
+    log.lines().filter_map(parse_diagnostic).collect()
+}
+
+pub fn get_build_log(program: &program::Program, device_id: cl_device_id) -> Result<BuildLog, String> {
+    get_program_build_log(program.get(), device_id)
+}
+
+// also works for handles opencl3 does not wrap, like the result of a failed link
+pub fn get_program_build_log(program: cl_program, device_id: cl_device_id) -> Result<BuildLog, String> {
+    let device_name = match device::Device::new(device_id).name() {
+        Ok(device_name) => device_name,
+        Err(error) => return Err(format!("not able to get device name: {}", error)),
+    };
+
+    let log = match program::get_program_build_info(program, device_id, program::CL_PROGRAM_BUILD_LOG) {
+        Ok(log) => String::from(log).trim_end_matches('\0').to_string(),
+        Err(error) => return Err(format!("not able to get build log: {}", error)),
+    };
+
+    Ok(BuildLog {
+        device: device_name.trim().to_string(),
+        diagnostics: parse_build_log(&log),
+        log,
+    })
+}
+
+pub fn get_build_logs(program: &program::Program, context: &context::Context) -> Result<Vec<BuildLog>, String> {
+    get_program_build_logs(program.get(), context)
+}
+
+pub fn get_program_build_logs(program: cl_program, context: &context::Context) -> Result<Vec<BuildLog>, String> {
+    let mut logs = Vec::new();
+    for device_id in context.devices() {
+        match get_program_build_log(program, *device_id) {
+            Ok(log) => logs.push(log),
+            Err(error) => return Err(error),
+        };
+    }
+
+    Ok(logs)
+}
+
+pub fn build_from_source(
+    context: &context::Context,
+    name: &str,
+    content: &str,
+    options: &str,
+) -> Result<(program::Program, Vec<BuildLog>), BuildFailure> {
+    let mut prog = match program::Program::create_from_source(context, content) {
+        Ok(prog) => prog,
+        Err(error) => {
+            return Err(BuildFailure {
+                source: name.to_string(),
+                error: error.to_string(),
+                logs: Vec::new(),
+            })
+        }
+    };
+
+    let built = prog.build(context.devices(), options);
+    let logs = get_build_logs(&prog, context).unwrap_or_default();
+
+    match built {
+        Ok(_) => Ok((prog, logs)),
+        Err(error) => Err(BuildFailure {
+            source: name.to_string(),
+            error: error.to_string(),
+            logs,
+        }),
+    }
+}
@@ -0,0 +1,110 @@
+use opencl3::context;
+use opencl3::device;
+use std::fmt;
+
+use crate::clvecadd::traits;
+
+pub const UNSUPPORTED_TYPE: &str = "unsupported type on device";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnsupportedType {
+    pub element_type: String,
+    pub device: String,
+    pub extension: String,
+}
+
+impl fmt::Display for UnsupportedType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} requires {}, which {} does not support",
+            UNSUPPORTED_TYPE, self.element_type, self.extension, self.device
+        )
+    }
+}
+
+impl From<UnsupportedType> for String {
+    fn from(unsupported: UnsupportedType) -> String {
+        unsupported.to_string()
+    }
+}
+
+// tells a missing extension, which callers may work around, apart from a failed query
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TypeSupportError {
+    Unsupported(UnsupportedType),
+    Query(String),
+}
+
+impl fmt::Display for TypeSupportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TypeSupportError::Unsupported(unsupported) => write!(f, "{}", unsupported),
+            TypeSupportError::Query(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl From<TypeSupportError> for String {
+    fn from(error: TypeSupportError) -> String {
+        error.to_string()
+    }
+}
+
+// OpenCL 3.0 devices may leave the extension out and only report the
+// __opencl_c_fp64 feature, a non-zero floating point config covers both
+pub fn supports_extension(device: &device::Device, extension: &str) -> Result<bool, String> {
+    let extensions = match device.extensions() {
+        Ok(extensions) => extensions,
+        Err(error) => return Err(format!("not able to get device extensions: {}", error)),
+    };
+    if extensions.split_whitespace().any(|name| name == extension) {
+        return Ok(true);
+    }
+
+    let config = match extension {
+        "cl_khr_fp64" => device.double_fp_config(),
+        "cl_khr_fp16" => device.half_fp_config(),
+        _ => return Ok(false),
+    };
+
+    match config {
+        Ok(config) => Ok(config != 0),
+        Err(_) => Ok(false),
+    }
+}
+
+pub fn check_type_support<T: traits::OpenclNum + traits::HasOpenclString>(
+    context: &context::Context,
+) -> Result<(), TypeSupportError> {
+    check_type_name_support(context, <T>::as_opencl_string())
+}
+
+pub fn check_type_name_support(context: &context::Context, element_type: &str) -> Result<(), TypeSupportError> {
+    let extension = match traits::get_required_extension(element_type) {
+        Some(extension) => extension,
+        None => return Ok(()),
+    };
+
+    for device_id in context.devices() {
+        let device = device::Device::new(*device_id);
+        match supports_extension(&device, extension) {
+            Ok(true) => (),
+            Ok(false) => {
+                let device_name = match device.name() {
+                    Ok(device_name) => device_name.trim().to_string(),
+                    Err(_) => String::from("device"),
+                };
+
+                return Err(TypeSupportError::Unsupported(UnsupportedType {
+                    element_type: element_type.to_string(),
+                    device: device_name,
+                    extension: extension.to_string(),
+                }));
+            }
+            Err(error) => return Err(TypeSupportError::Query(error)),
+        };
+    }
+
+    Ok(())
+}
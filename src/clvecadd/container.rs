@@ -0,0 +1,226 @@
+use opencl3::device;
+use opencl3::program;
+use opencl3::types::cl_device_id;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::clvecadd::options;
+
+const MAGIC: &str = "CLVECADD-BINARY 1";
+
+pub const DEFAULT_CACHE_DIR: &str = "src/bin/vecadd";
+pub const CACHE_DIR_ENV: &str = "CLVECADD_CACHE_DIR";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BinaryHeader {
+    pub device: String,
+    pub driver: String,
+    pub options_hash: u64,
+    pub source_hash: u64,
+}
+
+impl BinaryHeader {
+    pub fn new(device_id: cl_device_id, options: &str, source: &[u8]) -> Result<BinaryHeader, String> {
+        let (device, driver) = match get_device_identity(device_id) {
+            Ok(identity) => identity,
+            Err(error) => return Err(error),
+        };
+
+        Ok(BinaryHeader {
+            device,
+            driver,
+            options_hash: options::get_stable_hash(options.trim().as_bytes()),
+            source_hash: options::get_stable_hash(source),
+        })
+    }
+
+    // compares against the header expected for the current device, options and source
+    pub fn check(&self, expected: &BinaryHeader) -> Result<(), String> {
+        if self.device != expected.device {
+            return Err(format!(
+                "binary was built for device {}, not for {}",
+                self.device, expected.device
+            ));
+        }
+
+        if self.driver != expected.driver {
+            return Err(format!(
+                "binary was built with driver {}, current driver is {}",
+                self.driver, expected.driver
+            ));
+        }
+
+        if self.options_hash != expected.options_hash {
+            return Err(format!(
+                "binary was built with other options ({:016x}, expected {:016x})",
+                self.options_hash, expected.options_hash
+            ));
+        }
+
+        if self.source_hash != expected.source_hash {
+            return Err(format!(
+                "binary is out of date with its source ({:016x}, expected {:016x})",
+                self.source_hash, expected.source_hash
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+fn sanitize(field: &str) -> String {
+    field.trim_end_matches('\0').trim().replace(['\t', '\n'], " ")
+}
+
+fn get_device_identity(device_id: cl_device_id) -> Result<(String, String), String> {
+    let device = device::Device::new(device_id);
+
+    let device_name = match device.name() {
+        Ok(device_name) => device_name,
+        Err(error) => return Err(format!("not able to get device name: {}", error)),
+    };
+
+    let driver_version = match device.driver_version() {
+        Ok(driver_version) => driver_version,
+        Err(error) => return Err(format!("not able to get driver version: {}", error)),
+    };
+
+    Ok((sanitize(&device_name), sanitize(&driver_version)))
+}
+
+pub fn get_cache_dir() -> PathBuf {
+    match std::env::var(CACHE_DIR_ENV) {
+        Ok(path) => PathBuf::from(path),
+        Err(_) => PathBuf::from(DEFAULT_CACHE_DIR),
+    }
+}
+
+// the source hash is left out so an outdated binary is replaced in place
+pub fn get_binary_file_name(stem: &str, device: &str, driver: &str, options: &str) -> String {
+    let key = format!("{}\t{}\t{}", device, driver, options.trim());
+    format!("{}-{:016x}.bin", stem, options::get_stable_hash(key.as_bytes()))
+}
+
+pub fn get_binary_path(
+    cache_dir: &Path,
+    stem: &str,
+    device_id: cl_device_id,
+    options: &str,
+) -> Result<PathBuf, String> {
+    match get_device_identity(device_id) {
+        Ok((device, driver)) => Ok(cache_dir.join(get_binary_file_name(stem, &device, &driver, options))),
+        Err(error) => Err(error),
+    }
+}
+
+pub fn write_binary(program: &program::Program, path: &Path, options: &str, source: &[u8]) -> Result<(), String> {
+    let bins = match program.get_binaries() {
+        Ok(bins) => bins,
+        Err(error) => return Err(format!("not able to get binaries: {}", error)),
+    };
+
+    let bin = match bins.first() {
+        Some(bin) => bin,
+        None => return Err(String::from("no programs built")),
+    };
+
+    let device_id = match program.get_devices() {
+        Ok(devices) => match devices.first() {
+            Some(device_id) => *device_id as cl_device_id,
+            None => return Err(String::from("program has no devices")),
+        },
+        Err(error) => return Err(format!("not able to get program devices: {}", error)),
+    };
+
+    let header = match BinaryHeader::new(device_id, options, source) {
+        Ok(header) => header,
+        Err(error) => return Err(error),
+    };
+
+    if let Some(parent) = path.parent() {
+        match fs::create_dir_all(parent) {
+            Ok(_) => (),
+            Err(error) => return Err(format!("not able to create {}: {}", parent.display(), error)),
+        };
+    }
+
+    match fs::write(path, encode(&header, bin)) {
+        Ok(_) => Ok(()),
+        Err(error) => Err(format!("not able to write {}: {}", path.display(), error)),
+    }
+}
+
+// a text header of tab separated fields, an empty line, then the vendor binary
+pub fn encode(header: &BinaryHeader, binary: &[u8]) -> Vec<u8> {
+    let text = format!(
+        "{}\ndevice\t{}\ndriver\t{}\noptions\t{:016x}\nsource\t{:016x}\nsize\t{}\n\n",
+        MAGIC,
+        sanitize(&header.device),
+        sanitize(&header.driver),
+        header.options_hash,
+        header.source_hash,
+        binary.len()
+    );
+
+    let mut content = text.into_bytes();
+    content.extend_from_slice(binary);
+    content
+}
+
+pub fn decode(content: &[u8]) -> Result<(BinaryHeader, &[u8]), String> {
+    let end = match content.windows(2).position(|window| window == b"\n\n") {
+        Some(end) => end,
+        None => return Err(String::from("binary has no container header")),
+    };
+
+    let text = match std::str::from_utf8(&content[..end]) {
+        Ok(text) => text,
+        Err(_) => return Err(String::from("binary has no container header")),
+    };
+
+    let mut lines = text.lines();
+    if lines.next() != Some(MAGIC) {
+        return Err(String::from("binary has no container header"));
+    }
+
+    let mut device = None;
+    let mut driver = None;
+    let mut options_hash = None;
+    let mut source_hash = None;
+    let mut size = None;
+    for line in lines {
+        let (key, value) = match line.split_once('\t') {
+            Some(field) => field,
+            None => return Err(format!("malformed container header line: {}", line)),
+        };
+
+        match key {
+            "device" => device = Some(value.to_string()),
+            "driver" => driver = Some(value.to_string()),
+            "options" => options_hash = u64::from_str_radix(value, 16).ok(),
+            "source" => source_hash = u64::from_str_radix(value, 16).ok(),
+            "size" => size = value.parse::<usize>().ok(),
+            _ => (),
+        };
+    }
+
+    let binary = &content[end + 2..];
+    match (device, driver, options_hash, source_hash, size) {
+        (Some(device), Some(driver), Some(options_hash), Some(source_hash), Some(size)) => {
+            if size != binary.len() {
+                return Err(format!("binary is truncated, {} of {} bytes", binary.len(), size));
+            }
+
+            Ok((
+                BinaryHeader {
+                    device,
+                    driver,
+                    options_hash,
+                    source_hash,
+                },
+                binary,
+            ))
+        }
+        _ => Err(String::from("container header is incomplete")),
+    }
+}
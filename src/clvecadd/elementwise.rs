@@ -0,0 +1,197 @@
+use opencl3::command_queue;
+use opencl3::context;
+
+use crate::clvecadd::launch;
+use crate::clvecadd::options;
+use crate::clvecadd::registry;
+use crate::clvecadd::traits;
+
+pub const INPUT_NAMES: [&str; 3] = ["a", "b", "c"];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ElementwiseOp {
+    name: String,
+    arity: usize,
+    expression: String,
+    inplace: bool,
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) if first.is_ascii_alphabetic() || first == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    }
+}
+
+impl ElementwiseOp {
+    // the expression sees its inputs as a, b and c of type ARRAY_TYPE,
+    // for example ElementwiseOp::new("fma", 3, "a * b + c")
+    pub fn new(name: &str, arity: usize, expression: &str) -> ElementwiseOp {
+        ElementwiseOp {
+            name: name.to_string(),
+            arity,
+            expression: expression.to_string(),
+            inplace: false,
+        }
+    }
+
+    // the result is written back to the first input instead of a separate output
+    pub fn inplace(mut self, inplace: bool) -> ElementwiseOp {
+        self.inplace = inplace;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn arity(&self) -> usize {
+        self.arity
+    }
+
+    pub fn expression(&self) -> &str {
+        &self.expression
+    }
+
+    pub fn is_inplace(&self) -> bool {
+        self.inplace
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !is_identifier(&self.name) {
+            return Err(format!("{} is not a valid kernel name", self.name));
+        }
+
+        if self.arity == 0 || self.arity > INPUT_NAMES.len() {
+            return Err(format!(
+                "{} takes {} inputs, between 1 and {} are supported",
+                self.name,
+                self.arity,
+                INPUT_NAMES.len()
+            ));
+        }
+
+        // the expression is pasted into the kernel body, keep it a single expression
+        if self.expression.trim().is_empty() || self.expression.contains([';', '{', '}', '#']) {
+            return Err(format!("{} has an invalid expression: {}", self.name, self.expression));
+        }
+
+        Ok(())
+    }
+
+    pub fn generate_source(&self) -> Result<String, String> {
+        match self.validate() {
+            Ok(_) => (),
+            Err(error) => return Err(error),
+        };
+
+        let inputs = &INPUT_NAMES[..self.arity];
+        let mut params: Vec<String> = Vec::new();
+        for (index, input) in inputs.iter().enumerate() {
+            if self.inplace && index == 0 {
+                params.push(format!("__global ARRAY_TYPE *in_{}", input));
+            } else {
+                params.push(format!("__global const ARRAY_TYPE *in_{}", input));
+            }
+        }
+        if !self.inplace {
+            params.push(String::from("__global ARRAY_TYPE *out"));
+        }
+        params.push(String::from("ulong num"));
+
+        let mut body = String::new();
+        for input in inputs {
+            body.push_str(&format!("    const ARRAY_TYPE {} = in_{}[gid];\n", input, input));
+        }
+        let target = if self.inplace { "in_a" } else { "out" };
+        body.push_str(&format!("    {}[gid] = (ARRAY_TYPE)({});\n", target, self.expression.trim()));
+
+        Ok(format!(
+            "#ifndef ARRAY_TYPE\n\
+             #define ARRAY_TYPE float\n\
+             #endif\n\
+             \n\
+             #ifdef cl_khr_fp16\n\
+             #pragma OPENCL EXTENSION cl_khr_fp16: enable\n\
+             #endif\n\
+             \n\
+             #ifdef cl_khr_fp64\n\
+             #pragma OPENCL EXTENSION cl_khr_fp64: enable\n\
+             #endif\n\
+             \n\
+             __kernel void {}({}) {{\n\
+             \x20 for(unsigned long long int gid = get_global_id(0); gid < num; gid += get_global_size(0)) {{\n\
+             {}\
+             \x20 }}\n\
+             }}\n",
+            self.name,
+            params.join(", "),
+            body
+        ))
+    }
+
+    // names the cached program and binary, the hash tells apart equally named operations
+    pub fn get_stem(&self) -> Result<String, String> {
+        match self.generate_source() {
+            Ok(source) => Ok(format!(
+                "elementwise_{}_{:016x}",
+                self.name,
+                options::get_stable_hash(source.as_bytes())
+            )),
+            Err(error) => Err(error),
+        }
+    }
+}
+
+// inputs bind to a, b and c in order, for in-place operations `output` is input a
+// and `inputs` holds the remaining ones
+pub fn execute<T: Copy + traits::OpenclNum + traits::HasOpenclString>(
+    context: &context::Context,
+    queue: &command_queue::CommandQueue,
+    op: &ElementwiseOp,
+    inputs: &[&[T]],
+    output: &mut [T],
+) -> Result<(), String> {
+    let expected = if op.is_inplace() { op.arity() - 1 } else { op.arity() };
+    if inputs.len() != expected {
+        return Err(format!("{} takes {} inputs, {} given", op.name(), op.arity(), inputs.len()));
+    }
+
+    if let Some(input) = inputs.iter().find(|input| input.len() < output.len()) {
+        return Err(format!(
+            "{} got an input of {} elements for an output of {}",
+            op.name(),
+            input.len(),
+            output.len()
+        ));
+    }
+
+    let kernel = match registry::global_registry().get_generated::<T>(context, op) {
+        Ok(kernel) => kernel,
+        Err(error) => return Err(error),
+    };
+
+    let elements = output.len() as u64;
+    let mut args: Vec<Box<dyn launch::BoundArg + '_>> = Vec::new();
+    for input in inputs {
+        match launch::IntoKernelArg::into_arg(*input, context, queue) {
+            Ok(arg) => args.push(arg),
+            Err(error) => return Err(error),
+        };
+    }
+    // the output is input a for in-place operations and follows the inputs otherwise
+    let position = if op.is_inplace() { 0 } else { args.len() };
+    match launch::IntoKernelArg::into_arg(output, context, queue) {
+        Ok(arg) => args.insert(position, arg),
+        Err(error) => return Err(error),
+    };
+    match launch::IntoKernelArg::into_arg(elements, context, queue) {
+        Ok(arg) => args.push(arg),
+        Err(error) => return Err(error),
+    };
+
+    launch::launch(queue, &kernel, &mut args)
+}
@@ -0,0 +1,88 @@
+use opencl3::event;
+use opencl3::types::{cl_event, cl_uint};
+use std::marker::PhantomData;
+
+pub struct EventList<'a> {
+    cl_events: Vec<cl_event>,
+    _events: PhantomData<&'a event::Event>,
+}
+
+impl<'a> Default for EventList<'a> {
+    fn default() -> Self {
+        EventList::new()
+    }
+}
+
+impl<'a> EventList<'a> {
+    pub fn new() -> EventList<'a> {
+        EventList {
+            cl_events: Vec::new(),
+            _events: PhantomData,
+        }
+    }
+
+    pub fn push(&mut self, event: &'a event::Event) {
+        self.cl_events.push(event.get());
+    }
+
+    pub fn extend(&mut self, events: &'a [event::Event]) {
+        for event in events {
+            self.push(event);
+        }
+    }
+
+    pub fn merge(&mut self, other: &EventList<'a>) {
+        for cl_event in &other.cl_events {
+            if !self.cl_events.contains(cl_event) {
+                self.cl_events.push(*cl_event);
+            }
+        }
+    }
+
+    pub fn merged(mut self, other: &EventList<'a>) -> EventList<'a> {
+        self.merge(other);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.cl_events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cl_events.is_empty()
+    }
+
+    pub fn as_slice(&self) -> &[cl_event] {
+        &self.cl_events
+    }
+
+    pub fn count(&self) -> cl_uint {
+        self.cl_events.len() as cl_uint
+    }
+
+    pub fn as_ptr(&self) -> *const cl_event {
+        if !self.cl_events.is_empty() {
+            self.cl_events.as_ptr()
+        } else {
+            std::ptr::null()
+        }
+    }
+
+    pub fn as_raw(&self) -> (cl_uint, *const cl_event) {
+        (self.count(), self.as_ptr())
+    }
+}
+
+impl<'a> From<&'a [event::Event]> for EventList<'a> {
+    fn from(events: &'a [event::Event]) -> EventList<'a> {
+        let mut list = EventList::new();
+        list.extend(events);
+        list
+    }
+}
+
+impl<'a> From<&'a Vec<event::Event>> for EventList<'a> {
+    fn from(events: &'a Vec<event::Event>) -> EventList<'a> {
+        EventList::from(events.as_slice())
+    }
+}
@@ -4,9 +4,136 @@ use opencl3::context;
 use opencl3::command_queue;
 use opencl3::device;
 use opencl3::event;
+use std::collections::HashMap;
 use std::path::Path;
 use std::fs;
 use std::io::Read;
+use std::sync::{Mutex, OnceLock};
+use log::warn;
+
+use crate::clvecadd::buildlog;
+use crate::clvecadd::container;
+use crate::clvecadd::events;
+use crate::clvecadd::trace;
+use crate::clvecadd::tune;
+
+const DEFAULT_LOCAL_WORK_SIZE: usize = 256;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LocalSize {
+    Auto,
+    Driver,
+    Fixed(usize),
+    Strided { local: usize, elements_per_item: usize },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NdRange {
+    dims: u32,
+    global: [usize; 3],
+    local: [usize; 3],
+    offset: [usize; 3],
+    has_local: bool,
+    local_dims: usize,
+    offset_dims: usize,
+}
+
+impl NdRange {
+    pub fn new(global: &[usize]) -> NdRange {
+        let mut range = NdRange {
+            dims: global.len() as u32,
+            global: [1; 3],
+            local: [1; 3],
+            offset: [0; 3],
+            has_local: false,
+            local_dims: 0,
+            offset_dims: 0,
+        };
+        for (dim, size) in global.iter().take(3).enumerate() {
+            range.global[dim] = *size;
+        }
+        range
+    }
+
+    pub fn with_local(mut self, local: &[usize]) -> NdRange {
+        for (dim, size) in local.iter().take(3).enumerate() {
+            self.local[dim] = *size;
+        }
+        self.has_local = true;
+        self.local_dims = local.len();
+        self
+    }
+
+    pub fn with_offset(mut self, offset: &[usize]) -> NdRange {
+        for (dim, start) in offset.iter().take(3).enumerate() {
+            self.offset[dim] = *start;
+        }
+        self.offset_dims = offset.len();
+        self
+    }
+
+    pub fn dims(&self) -> u32 {
+        self.dims
+    }
+
+    // more than three dimensions are rejected by validate, the accessors
+    // only expose the ones that are stored
+    fn stored_dims(&self) -> usize {
+        std::cmp::min(self.dims as usize, 3)
+    }
+
+    pub fn global(&self) -> &[usize] {
+        &self.global[..self.stored_dims()]
+    }
+
+    pub fn local(&self) -> Option<&[usize]> {
+        if self.has_local {
+            Some(&self.local[..self.stored_dims()])
+        } else {
+            None
+        }
+    }
+
+    pub fn offset(&self) -> &[usize] {
+        &self.offset[..self.stored_dims()]
+    }
+
+    pub fn get_rounded_global(&self) -> [usize; 3] {
+        let mut global = self.global;
+        if self.has_local {
+            for (global, local) in global.iter_mut().zip(&self.local).take(self.stored_dims()) {
+                *global = get_global_work_size(*local, *global);
+            }
+        }
+        global
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.dims < 1 || self.dims > 3 {
+            return Err(format!("nd range must have 1 to 3 dimensions, got {}", self.dims));
+        }
+
+        if self.has_local && self.local_dims != self.dims as usize {
+            return Err(format!(
+                "local size has {} dimensions, global size has {}",
+                self.local_dims, self.dims
+            ));
+        }
+
+        if self.offset_dims != 0 && self.offset_dims != self.dims as usize {
+            return Err(format!(
+                "global offset has {} dimensions, global size has {}",
+                self.offset_dims, self.dims
+            ));
+        }
+
+        if self.has_local && self.local.contains(&0) {
+            return Err(String::from("local size must not be zero"));
+        }
+
+        Ok(())
+    }
+}
 
 pub fn create_queue(
     context: &context::Context,
@@ -58,30 +185,41 @@ pub fn create_and_build_from_sources(
             }
         };
 
-        programs.push(
-            match program::Program::create_and_build_from_source(&context, &content, &options) {
-                Ok(program) => program,
-                Err(error) => {
-                    return Err(format!(
-                        "error building source file {}: {}",
-                        sourcestr, error
-                    ))
-                }
-            },
-        );
+        let (program, logs) = match buildlog::build_from_source(context, sourcestr, &content, options) {
+            Ok(built) => built,
+            Err(failure) => return Err(failure.into()),
+        };
+
+        for log in &logs {
+            for warning in log.warnings() {
+                warn!("{}: {}", log.device, warning);
+            }
+        }
+        programs.push(program);
     }
 
     Ok(programs)
 }
 
+// binaries are containers written by container::encode, each is checked against the
+// devices of the context, the options and the kernel source it was built from
 pub fn create_and_build_from_binaries(
     context: &context::Context,
     sources: &[&Path],
     options: &str,
+    kernel_sources: &[&Path],
 ) -> Result<Vec<program::Program>, String> {
     let mut programs: Vec<program::Program> = Vec::new();
 
-    for source in sources {
+    if sources.len() != kernel_sources.len() {
+        return Err(format!(
+            "{} binaries given for {} kernel sources",
+            sources.len(),
+            kernel_sources.len()
+        ));
+    }
+
+    for (source, kernel_source) in sources.iter().zip(kernel_sources) {
         let sourcestr = match source.to_str().ok_or_else(|| 0) {
             Ok(sourcestr) => sourcestr,
             Err(error) => return Err(format!("no valid path given: {}", error)),
@@ -115,16 +253,20 @@ pub fn create_and_build_from_binaries(
             }
         };
 
+        let kernel_content = match fs::read(kernel_source) {
+            Ok(kernel_content) => kernel_content,
+            Err(error) => {
+                return Err(format!(
+                    "error reading source file {}: {}",
+                    kernel_source.display(), error
+                ))
+            }
+        };
+
         programs.push(
-            match program::Program::create_and_build_from_binary(&context, &[&buffer[..]], &options)
-            {
+            match create_and_build_from_container(context, sourcestr, &buffer, options, &kernel_content) {
                 Ok(program) => program,
-                Err(error) => {
-                    return Err(format!(
-                        "error building source file {}: {}",
-                        sourcestr, error
-                    ))
-                }
+                Err(error) => return Err(error),
             },
         );
     }
@@ -132,44 +274,303 @@ pub fn create_and_build_from_binaries(
     Ok(programs)
 }
 
+pub fn create_and_build_from_container(
+    context: &context::Context,
+    name: &str,
+    content: &[u8],
+    options: &str,
+    kernel_content: &[u8],
+) -> Result<program::Program, String> {
+    let (header, binary) = match container::decode(content) {
+        Ok(decoded) => decoded,
+        Err(error) => return Err(format!("cached binary {} rejected: {}", name, error)),
+    };
+
+    for device_id in context.devices() {
+        let expected = match container::BinaryHeader::new(*device_id, options, kernel_content) {
+            Ok(expected) => expected,
+            Err(error) => return Err(error),
+        };
+
+        match header.check(&expected) {
+            Ok(_) => (),
+            Err(error) => return Err(format!("cached binary {} rejected: {}", name, error)),
+        };
+    }
+
+    // the header matched every device, so each of them gets the same binary
+    let binaries = vec![binary; context.devices().len()];
+    match program::Program::create_and_build_from_binary(context, &binaries, options) {
+        Ok(program) => Ok(program),
+        Err(error) => Err(format!("error building source file {}: {}", name, error)),
+    }
+}
+
 pub fn execute_kernel(
     queue: &command_queue::CommandQueue,
     kernel: &kernel::Kernel,
     elements: usize,
-    wait: &Vec<event::Event>,
+    wait: impl AsRef<[event::Event]>,
+) -> Result<event::Event, String> {
+    enqueue_kernel(queue, kernel, elements, LocalSize::Auto, &events::EventList::from(wait.as_ref()))
+}
+
+pub fn execute_kernel_with_local_size(
+    queue: &command_queue::CommandQueue,
+    kernel: &kernel::Kernel,
+    elements: usize,
+    local_size: LocalSize,
+    wait: impl AsRef<[event::Event]>,
 ) -> Result<event::Event, String> {
-    let local_work_size = [256 as usize];
-    let global_work_size = [get_global_work_size(256, elements) as usize];
+    enqueue_kernel(queue, kernel, elements, local_size, &events::EventList::from(wait.as_ref()))
+}
 
-    let mut cl_events: Vec<opencl3::types::cl_event> = Vec::new();
-    for event in wait {
-        cl_events.push(event.get());
+pub fn enqueue_kernel(
+    queue: &command_queue::CommandQueue,
+    kernel: &kernel::Kernel,
+    elements: usize,
+    local_size: LocalSize,
+    wait_list: &events::EventList,
+) -> Result<event::Event, String> {
+    // a zero global size is invalid before OpenCL 2.1, an empty launch only
+    // needs an event that completes after its wait list
+    if elements == 0 {
+        return unsafe {
+            match command_queue::enqueue_marker_with_wait_list(queue.get(), wait_list.count(), wait_list.as_ptr()) {
+                Ok(event) => Ok(event::Event::from(event)),
+                Err(error) => Err(format!("error enqueueing marker for empty launch: {}", error)),
+            }
+        };
     }
 
+    let (local, elements_per_item) = match local_size {
+        LocalSize::Auto => match get_launch_config(queue, kernel, elements) {
+            Ok((local, elements_per_item)) => (Some(local), elements_per_item),
+            Err(error) => return Err(error),
+        },
+        LocalSize::Fixed(local) => (Some(local), 1),
+        LocalSize::Strided { local, elements_per_item } => (Some(local), std::cmp::max(elements_per_item, 1)),
+        LocalSize::Driver => (None, 1),
+    };
+
+    // with more than one element per item the kernel covers the remaining
+    // elements with a grid-stride loop, as every kernel in vecadd.cl does
+    let items = elements.div_ceil(elements_per_item);
+    let range = match local {
+        Some(local) => NdRange::new(&[items]).with_local(&[local]),
+        None => NdRange::new(&[items]),
+    };
+
+    enqueue_nd_range(queue, kernel, &range, wait_list)
+}
+
+pub fn execute_kernel_over_region(
+    queue: &command_queue::CommandQueue,
+    kernel: &kernel::Kernel,
+    region: [usize; 3],
+    wait: impl AsRef<[event::Event]>,
+) -> Result<event::Event, String> {
+    let range = if region[2] > 1 {
+        NdRange::new(&region)
+    } else {
+        NdRange::new(&region[..2])
+    };
+
+    enqueue_nd_range(queue, kernel, &range, &events::EventList::from(wait.as_ref()))
+}
+
+pub fn execute_nd_range(
+    queue: &command_queue::CommandQueue,
+    kernel: &kernel::Kernel,
+    range: &NdRange,
+    wait: impl AsRef<[event::Event]>,
+) -> Result<event::Event, String> {
+    enqueue_nd_range(queue, kernel, range, &events::EventList::from(wait.as_ref()))
+}
+
+pub fn enqueue_nd_range(
+    queue: &command_queue::CommandQueue,
+    kernel: &kernel::Kernel,
+    range: &NdRange,
+    wait_list: &events::EventList,
+) -> Result<event::Event, String> {
+    match range.validate() {
+        Ok(_) => (),
+        Err(error) => return Err(error),
+    };
+
+    let global_work_size = range.get_rounded_global();
+    let local_work_size = range.local;
+
     unsafe {
         let event = match command_queue::enqueue_nd_range_kernel(
             queue.get(),
             kernel.get(),
-            1,
-            std::ptr::null(),
+            range.dims,
+            range.offset.as_ptr(),
             global_work_size.as_ptr(),
-            local_work_size.as_ptr(),
-            cl_events.len() as u32,
-            if !cl_events.is_empty() {
-                cl_events.as_ptr()
+            if range.has_local {
+                local_work_size.as_ptr()
             } else {
                 std::ptr::null()
             },
+            wait_list.count(),
+            wait_list.as_ptr(),
         ) {
             Ok(event) => event,
             Err(error) => return Err(format!("error executing kernel: {}", error)),
         };
 
-        Ok(event::Event::from(event))
+        let event = event::Event::from(event);
+        trace::record_kernel(queue, &event, kernel, wait_list);
+        Ok(event)
+    }
+}
+
+#[derive(Clone, Debug)]
+struct LaunchInfo {
+    tuning: Option<tune::TuningKey>,
+    limit: usize,
+    preferred: usize,
+}
+
+// kernels are created for every launch, so the cache is keyed by device,
+// program and kernel name instead of the kernel handle
+type LaunchInfos = HashMap<(usize, usize, String), LaunchInfo>;
+
+fn launch_infos() -> &'static Mutex<LaunchInfos> {
+    static INFOS: OnceLock<Mutex<LaunchInfos>> = OnceLock::new();
+    INFOS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn get_launch_info(
+    queue: &command_queue::CommandQueue,
+    kernel: &kernel::Kernel,
+    elements: usize,
+) -> Result<LaunchInfo, String> {
+    let device_id = match queue.device() {
+        Ok(device_id) => device_id,
+        Err(error) => return Err(format!("not able to get device of queue: {}", error)),
+    };
+
+    let prog = match kernel.program() {
+        Ok(prog) => prog,
+        Err(error) => return Err(format!("not able to get kernel program: {}", error)),
+    };
+
+    let kernel_name = match kernel.function_name() {
+        Ok(kernel_name) => kernel_name,
+        Err(error) => return Err(format!("not able to get kernel name: {}", error)),
+    };
+
+    let cache_key = (device_id as usize, prog as usize, kernel_name);
+    if let Ok(infos) = launch_infos().lock() {
+        if let Some(info) = infos.get(&cache_key) {
+            return Ok(info.clone());
+        }
+    }
+
+    let (limit, preferred) = match get_work_group_limits(queue, kernel) {
+        Ok(limits) => limits,
+        Err(error) => return Err(error),
+    };
+
+    let info = LaunchInfo {
+        tuning: tune::get_tuning_key(queue, kernel, elements).ok(),
+        limit,
+        preferred,
+    };
+
+    if let Ok(mut infos) = launch_infos().lock() {
+        infos.insert(cache_key, info.clone());
+    }
+    Ok(info)
+}
+
+pub fn get_launch_config(
+    queue: &command_queue::CommandQueue,
+    kernel: &kernel::Kernel,
+    elements: usize,
+) -> Result<(usize, usize), String> {
+    let info = match get_launch_info(queue, kernel, elements) {
+        Ok(info) => info,
+        Err(error) => return Err(error),
+    };
+
+    if let Some(mut key) = info.tuning {
+        key.size_bucket = tune::get_size_bucket(elements);
+        if let Some(entry) = tune::lookup(&key) {
+            return Ok((entry.local, std::cmp::max(entry.elements_per_item, 1)));
+        }
+    }
+
+    Ok((choose_local_work_size(info.limit, info.preferred, elements), 1))
+}
+
+pub fn get_local_work_size(
+    queue: &command_queue::CommandQueue,
+    kernel: &kernel::Kernel,
+    elements: usize,
+) -> Result<usize, String> {
+    match get_work_group_limits(queue, kernel) {
+        Ok((limit, preferred)) => Ok(choose_local_work_size(limit, preferred, elements)),
+        Err(error) => Err(error),
+    }
+}
+
+fn get_work_group_limits(
+    queue: &command_queue::CommandQueue,
+    kernel: &kernel::Kernel,
+) -> Result<(usize, usize), String> {
+    let device_id = match queue.device() {
+        Ok(device_id) => device_id,
+        Err(error) => return Err(format!("not able to get device of queue: {}", error)),
+    };
+    let device = device::Device::new(device_id);
+
+    let kernel_max = match kernel.get_work_group_size(device_id) {
+        Ok(kernel_max) => kernel_max,
+        Err(error) => return Err(format!("not able to get kernel work-group size: {}", error)),
+    };
+
+    let preferred = match kernel.get_work_group_size_multiple(device_id) {
+        Ok(preferred) => preferred,
+        Err(error) => return Err(format!("not able to get preferred work-group size multiple: {}", error)),
+    };
+
+    let device_max = match device.max_work_group_size() {
+        Ok(device_max) => device_max,
+        Err(error) => return Err(format!("not able to get device work-group size: {}", error)),
+    };
+
+    let item_max = match device.max_work_item_sizes() {
+        Ok(item_sizes) => match item_sizes.first() {
+            Some(item_max) => *item_max,
+            None => device_max,
+        },
+        Err(error) => return Err(format!("not able to get device work-item sizes: {}", error)),
+    };
+
+    let limit = std::cmp::min(kernel_max, std::cmp::min(device_max, item_max));
+    Ok((limit, preferred))
+}
+
+pub fn choose_local_work_size(limit: usize, preferred: usize, elements: usize) -> usize {
+    let limit = std::cmp::max(limit, 1);
+    let preferred = std::cmp::max(preferred, 1);
+    let elements = std::cmp::max(elements, 1);
+
+    let wanted = std::cmp::min(DEFAULT_LOCAL_WORK_SIZE, get_global_work_size(preferred, elements));
+    let local = std::cmp::min(wanted, limit);
+    if local >= preferred {
+        local - local % preferred
+    } else {
+        local
     }
 }
 
 pub fn get_global_work_size(local: usize, elements: usize) -> usize {
+    let local = std::cmp::max(local, 1);
     let mult = (elements + local - 1) / local;
     mult * local
 }
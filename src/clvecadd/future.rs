@@ -0,0 +1,100 @@
+use opencl3::event;
+use opencl3::types::{cl_event, cl_int};
+use std::ffi::c_void;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+struct EventState {
+    status: Option<cl_int>,
+    waker: Option<Waker>,
+}
+
+pub struct EventFuture {
+    event: event::Event,
+    state: Arc<Mutex<EventState>>,
+}
+
+extern "C" fn notify_event_complete(_event: cl_event, status: cl_int, user_data: *mut c_void) {
+    let state = unsafe { Arc::from_raw(user_data as *const Mutex<EventState>) };
+    let waker = match state.lock() {
+        Ok(mut state) => {
+            state.status = Some(status);
+            state.waker.take()
+        }
+        Err(_) => None,
+    };
+
+    if let Some(waker) = waker {
+        waker.wake();
+    }
+}
+
+impl EventFuture {
+    pub fn new(event: event::Event) -> Result<EventFuture, String> {
+        let state = Arc::new(Mutex::new(EventState {
+            status: None,
+            waker: None,
+        }));
+
+        let user_data = Arc::into_raw(Arc::clone(&state)) as *mut c_void;
+        match event.set_callback(event::CL_COMPLETE, notify_event_complete, user_data) {
+            Ok(_) => (),
+            Err(error) => {
+                unsafe { drop(Arc::from_raw(user_data as *const Mutex<EventState>)) };
+                return Err(format!("not able to set event callback: {}", error));
+            }
+        };
+
+        Ok(EventFuture { event, state })
+    }
+
+    pub fn event(&self) -> &event::Event {
+        &self.event
+    }
+
+    fn is_complete(&self) -> bool {
+        match self.state.lock() {
+            Ok(state) => state.status.is_some(),
+            Err(_) => false,
+        }
+    }
+}
+
+impl Future for EventFuture {
+    type Output = Result<(), String>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(error) => return Poll::Ready(Err(format!("event state lock poisoned: {}", error))),
+        };
+
+        match state.status {
+            Some(status) if status < 0 => Poll::Ready(Err(format!("event failed with status: {}", status))),
+            Some(_) => Poll::Ready(Ok(())),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Drop for EventFuture {
+    fn drop(&mut self) {
+        // host memory referenced by the command may be freed right after this
+        if !self.is_complete() {
+            let _ = self.event.wait();
+        }
+    }
+}
+
+impl TryFrom<event::Event> for EventFuture {
+    type Error = String;
+
+    fn try_from(event: event::Event) -> Result<EventFuture, String> {
+        EventFuture::new(event)
+    }
+}
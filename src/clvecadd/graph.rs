@@ -0,0 +1,230 @@
+use opencl3::command_queue;
+use opencl3::event;
+use opencl3::kernel;
+use opencl3::memory;
+use opencl3::memory::ClMem;
+use opencl3::types::cl_mem;
+use std::collections::HashMap;
+
+use crate::clvecadd::buffer;
+use crate::clvecadd::events;
+use crate::clvecadd::exec;
+use crate::clvecadd::trace;
+
+pub type NodeId = usize;
+
+type Enqueue<'a> =
+    Box<dyn FnOnce(&command_queue::CommandQueue, &events::EventList) -> Result<event::Event, String> + 'a>;
+
+struct Node<'a> {
+    name: String,
+    kernel: Option<String>,
+    bytes: usize,
+    dependencies: Vec<NodeId>,
+    enqueue: Enqueue<'a>,
+}
+
+#[derive(Default)]
+struct BufferAccess {
+    last_writer: Option<NodeId>,
+    readers: Vec<NodeId>,
+}
+
+#[derive(Default)]
+pub struct TaskGraph<'a> {
+    nodes: Vec<Node<'a>>,
+    accesses: HashMap<cl_mem, BufferAccess>,
+}
+
+impl<'a> TaskGraph<'a> {
+    pub fn new() -> TaskGraph<'a> {
+        TaskGraph {
+            nodes: Vec::new(),
+            accesses: HashMap::new(),
+        }
+    }
+
+    pub fn write<T: Copy>(&mut self, buffer: &'a memory::Buffer<T>, input: &'a [T]) -> NodeId {
+        let node = self.nodes.len();
+        let mut dependencies = Vec::new();
+        self.track_write(node, buffer.get(), &mut dependencies);
+
+        self.nodes.push(Node {
+            name: String::from("write_buffer"),
+            kernel: None,
+            bytes: std::mem::size_of_val(input),
+            dependencies,
+            enqueue: Box::new(move |queue, wait_list| {
+                buffer::enqueue_write_buffer(queue, buffer, input, wait_list)
+            }),
+        });
+        node
+    }
+
+    pub fn read<T: Copy>(&mut self, buffer: &'a memory::Buffer<T>, output: &'a mut [T]) -> NodeId {
+        let node = self.nodes.len();
+        let mut dependencies = Vec::new();
+        self.track_read(node, buffer.get(), &mut dependencies);
+
+        self.nodes.push(Node {
+            name: String::from("read_buffer"),
+            kernel: None,
+            bytes: std::mem::size_of_val(output),
+            dependencies,
+            enqueue: Box::new(move |queue, wait_list| {
+                buffer::enqueue_read_buffer(queue, buffer, output, wait_list)
+            }),
+        });
+        node
+    }
+
+    pub fn launch(
+        &mut self,
+        kernel: &'a kernel::Kernel,
+        elements: usize,
+        reads: &[&dyn ClMem],
+        writes: &[&dyn ClMem],
+    ) -> NodeId {
+        let node = self.nodes.len();
+        let mut dependencies = Vec::new();
+        for mem in reads {
+            self.track_read(node, mem.get(), &mut dependencies);
+        }
+        for mem in writes {
+            self.track_write(node, mem.get(), &mut dependencies);
+        }
+
+        let name = match kernel.function_name() {
+            Ok(name) => name,
+            Err(_) => String::from("kernel"),
+        };
+
+        self.nodes.push(Node {
+            name: name.clone(),
+            kernel: Some(name),
+            bytes: 0,
+            dependencies,
+            enqueue: Box::new(move |queue, wait_list| {
+                exec::enqueue_kernel(queue, kernel, elements, exec::LocalSize::Auto, wait_list)
+            }),
+        });
+        node
+    }
+
+    pub fn after(&mut self, node: NodeId, dependency: NodeId) -> Result<(), String> {
+        if node >= self.nodes.len() || dependency >= node {
+            return Err(format!(
+                "node {} cannot depend on node {}, dependencies must point to earlier nodes",
+                node, dependency
+            ));
+        }
+
+        add_dependency(&mut self.nodes[node].dependencies, dependency);
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn name(&self, node: NodeId) -> Option<&str> {
+        self.nodes.get(node).map(|node| node.name.as_str())
+    }
+
+    pub fn dependencies(&self, node: NodeId) -> Option<&[NodeId]> {
+        self.nodes.get(node).map(|node| node.dependencies.as_slice())
+    }
+
+    pub fn submit(self, queue: &command_queue::CommandQueue) -> Result<Vec<event::Event>, String> {
+        self.submit_impl(queue, None)
+    }
+
+    pub fn submit_traced(
+        self,
+        queue: &command_queue::CommandQueue,
+        recorder: &mut trace::TraceRecorder,
+    ) -> Result<Vec<event::Event>, String> {
+        self.submit_impl(queue, Some(recorder))
+    }
+
+    fn submit_impl(
+        self,
+        queue: &command_queue::CommandQueue,
+        mut recorder: Option<&mut trace::TraceRecorder>,
+    ) -> Result<Vec<event::Event>, String> {
+        let mut submitted: Vec<event::Event> = Vec::new();
+        let mut records: Vec<trace::RecordId> = Vec::new();
+
+        for (id, node) in self.nodes.into_iter().enumerate() {
+            let event = {
+                let mut wait_list = events::EventList::new();
+                for dependency in &node.dependencies {
+                    wait_list.push(&submitted[*dependency]);
+                }
+
+                match (node.enqueue)(queue, &wait_list) {
+                    Ok(event) => event,
+                    Err(error) => {
+                        wait_for_submitted(&submitted);
+                        return Err(format!("error submitting node {} ({}): {}", id, node.name, error));
+                    }
+                }
+            };
+
+            if let Some(recorder) = recorder.as_mut() {
+                let dependencies: Vec<trace::RecordId> =
+                    node.dependencies.iter().map(|dependency| records[*dependency]).collect();
+                match recorder.record(queue, &event, &node.name, node.kernel.as_deref(), node.bytes, &dependencies) {
+                    Ok(record) => records.push(record),
+                    Err(error) => {
+                        submitted.push(event);
+                        wait_for_submitted(&submitted);
+                        return Err(error);
+                    }
+                };
+            }
+            submitted.push(event);
+        }
+
+        Ok(submitted)
+    }
+
+    fn track_read(&mut self, node: NodeId, mem: cl_mem, dependencies: &mut Vec<NodeId>) {
+        let access = self.accesses.entry(mem).or_default();
+        if let Some(writer) = access.last_writer {
+            add_dependency(dependencies, writer);
+        }
+        access.readers.push(node);
+    }
+
+    fn track_write(&mut self, node: NodeId, mem: cl_mem, dependencies: &mut Vec<NodeId>) {
+        let access = self.accesses.entry(mem).or_default();
+        if let Some(writer) = access.last_writer {
+            add_dependency(dependencies, writer);
+        }
+        for reader in access.readers.drain(..) {
+            if reader != node {
+                add_dependency(dependencies, reader);
+            }
+        }
+        access.last_writer = Some(node);
+    }
+}
+
+// submitted nodes still use host memory borrowed by the graph, so they have to
+// finish before an error hands that memory back to the caller
+fn wait_for_submitted(submitted: &[event::Event]) {
+    for event in submitted {
+        let _ = event.wait();
+    }
+}
+
+fn add_dependency(dependencies: &mut Vec<NodeId>, dependency: NodeId) {
+    if !dependencies.contains(&dependency) {
+        dependencies.push(dependency);
+    }
+}
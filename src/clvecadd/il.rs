@@ -0,0 +1,108 @@
+use opencl3::context;
+use opencl3::device;
+use opencl3::program;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::clvecadd::options;
+
+const SPIRV_MAGIC: u32 = 0x07230203;
+
+// CL_DEVICE_IL_VERSION is a space separated list such as "SPIR-V_1.0 SPIR-V_1.2",
+// devices before OpenCL 2.1 do not know the query and report no IL support
+pub fn get_il_versions(device: &device::Device) -> Vec<String> {
+    match device.il_version() {
+        Ok(versions) => versions
+            .split_whitespace()
+            .map(|version| version.trim_end_matches('\0').to_string())
+            .filter(|version| !version.is_empty())
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+pub fn supports_spirv(context: &context::Context) -> bool {
+    !context.devices().is_empty()
+        && context.devices().iter().all(|device_id| {
+            get_il_versions(&device::Device::new(*device_id))
+                .iter()
+                .any(|version| version.starts_with("SPIR-V"))
+        })
+}
+
+pub fn is_spirv(il: &[u8]) -> bool {
+    if il.len() < 20 || !il.len().is_multiple_of(4) {
+        return false;
+    }
+
+    let magic = [il[0], il[1], il[2], il[3]];
+    u32::from_le_bytes(magic) == SPIRV_MAGIC || u32::from_be_bytes(magic) == SPIRV_MAGIC
+}
+
+// scripts/build_spirv.sh writes the hash of the source next to each module
+pub fn get_source_hash_path(module: &Path) -> PathBuf {
+    let mut path = module.as_os_str().to_owned();
+    path.push(".hash");
+    PathBuf::from(path)
+}
+
+// a module compiled from an older source must not be used, or cached, in its place
+pub fn check_module_source(module: &Path, source: &Path) -> Result<(), String> {
+    let hash_path = get_source_hash_path(module);
+    let recorded = match fs::read_to_string(&hash_path) {
+        Ok(recorded) => recorded.trim().to_string(),
+        Err(error) => return Err(format!("not able to read source hash {}: {}", hash_path.display(), error)),
+    };
+
+    let content = match fs::read(source) {
+        Ok(content) => content,
+        Err(error) => return Err(format!("error reading source file {}: {}", source.display(), error)),
+    };
+
+    let current = format!("{:016x}", options::get_stable_hash(&content));
+    if recorded != current {
+        return Err(format!(
+            "IL module {} was built from source {}, {} is at {}, rerun scripts/build_spirv.sh",
+            module.display(),
+            recorded,
+            source.display(),
+            current
+        ));
+    }
+
+    Ok(())
+}
+
+pub fn create_and_build_from_il(
+    context: &context::Context,
+    sources: &[&Path],
+    options: &str,
+) -> Result<Vec<program::Program>, String> {
+    if !supports_spirv(context) {
+        return Err(String::from("devices of context do not accept SPIR-V"));
+    }
+
+    let mut programs: Vec<program::Program> = Vec::new();
+
+    for source in sources {
+        if !source.is_file() {
+            return Err(format!("IL module {} does not exist or is not a file", source.display()));
+        }
+
+        let il = match fs::read(source) {
+            Ok(il) => il,
+            Err(error) => return Err(format!("error reading IL module {}: {}", source.display(), error)),
+        };
+
+        if !is_spirv(&il) {
+            return Err(format!("{} is not a SPIR-V module", source.display()));
+        }
+
+        programs.push(match program::Program::create_and_build_from_il(context, &il, options) {
+            Ok(program) => program,
+            Err(error) => return Err(format!("error building IL module {}: {}", source.display(), error)),
+        });
+    }
+
+    Ok(programs)
+}
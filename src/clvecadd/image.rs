@@ -0,0 +1,280 @@
+use opencl3::context;
+use opencl3::command_queue;
+use opencl3::event;
+use opencl3::memory;
+use opencl3::memory::ClMem;
+use opencl3::types::{cl_addressing_mode, cl_filter_mode, cl_image_desc, CL_FALSE, CL_TRUE};
+use std::ffi::c_void;
+
+use crate::clvecadd::events;
+use crate::clvecadd::buffer;
+use crate::clvecadd::trace;
+use crate::clvecadd::traits;
+
+#[derive(Clone, Copy)]
+pub enum ImageOp {
+    Add,
+    Sub,
+}
+
+pub enum AddressingMode {
+    None,
+    ClampToEdge,
+    Clamp,
+    Repeat,
+    MirroredRepeat,
+}
+
+pub enum FilterMode {
+    Nearest,
+    Linear,
+}
+
+pub struct SamplerBuilder {
+    normalized_coords: bool,
+    addressing_mode: cl_addressing_mode,
+    filter_mode: cl_filter_mode,
+}
+
+impl Default for SamplerBuilder {
+    fn default() -> Self {
+        SamplerBuilder::new()
+    }
+}
+
+impl SamplerBuilder {
+    pub fn new() -> SamplerBuilder {
+        SamplerBuilder {
+            normalized_coords: false,
+            addressing_mode: memory::CL_ADDRESS_NONE,
+            filter_mode: memory::CL_FILTER_NEAREST,
+        }
+    }
+
+    pub fn normalized_coords(mut self, normalized: bool) -> SamplerBuilder {
+        self.normalized_coords = normalized;
+        self
+    }
+
+    pub fn addressing_mode(mut self, mode: AddressingMode) -> SamplerBuilder {
+        self.addressing_mode = match mode {
+            AddressingMode::None => memory::CL_ADDRESS_NONE,
+            AddressingMode::ClampToEdge => memory::CL_ADDRESS_CLAMP_TO_EDGE,
+            AddressingMode::Clamp => memory::CL_ADDRESS_CLAMP,
+            AddressingMode::Repeat => memory::CL_ADDRESS_REPEAT,
+            AddressingMode::MirroredRepeat => memory::CL_ADDRESS_MIRRORED_REPEAT,
+        };
+        self
+    }
+
+    pub fn filter_mode(mut self, mode: FilterMode) -> SamplerBuilder {
+        self.filter_mode = match mode {
+            FilterMode::Nearest => memory::CL_FILTER_NEAREST,
+            FilterMode::Linear => memory::CL_FILTER_LINEAR,
+        };
+        self
+    }
+
+    #[allow(deprecated)]
+    pub fn build(&self, context: &context::Context) -> Result<memory::Sampler, String> {
+        let normalized = if self.normalized_coords {
+            CL_TRUE
+        } else {
+            CL_FALSE
+        };
+
+        match memory::Sampler::create(context, normalized, self.addressing_mode, self.filter_mode) {
+            Ok(sampler) => Ok(sampler),
+            Err(error) => Err(format!("error creating sampler: {}", error)),
+        }
+    }
+}
+
+pub fn get_kernel_name(op: ImageOp, region: [usize; 3]) -> &'static str {
+    match (op, region[2] > 1) {
+        (ImageOp::Add, false) => "addImages2d",
+        (ImageOp::Sub, false) => "subImages2d",
+        (ImageOp::Add, true) => "addImages3d",
+        (ImageOp::Sub, true) => "subImages3d",
+    }
+}
+
+pub fn get_pixel_type<T: traits::HasImageFormat>() -> &'static str {
+    match <T>::image_access_suffix() {
+        "i" => "int4",
+        "ui" => "uint4",
+        _ => "float4",
+    }
+}
+
+pub fn create_image<T: traits::HasImageFormat>(
+    context: &context::Context,
+    input: &mut Vec<T>,
+    region: [usize; 3],
+    mode: buffer::MemMode,
+) -> Result<memory::Image, String> {
+    let [width, height, depth] = region;
+    if width * height * depth != input.len() {
+        return Err(format!(
+            "image of {}x{}x{} does not match {} host elements",
+            width,
+            height,
+            depth,
+            input.len()
+        ));
+    }
+
+    let alloc_flag = memory::CL_MEM_USE_HOST_PTR;
+    let mem_flag = buffer::get_mem_flag(mode);
+    let format = <T>::image_format();
+    let desc = cl_image_desc {
+        image_type: if depth > 1 {
+            memory::CL_MEM_OBJECT_IMAGE3D
+        } else {
+            memory::CL_MEM_OBJECT_IMAGE2D
+        },
+        image_width: width,
+        image_height: height,
+        image_depth: if depth > 1 { depth } else { 0 },
+        image_array_size: 0,
+        image_row_pitch: 0,
+        image_slice_pitch: 0,
+        num_mip_levels: 0,
+        num_samples: 0,
+        buffer: std::ptr::null_mut(),
+    };
+
+    unsafe {
+        let data = input.as_mut_ptr() as *mut c_void;
+        let image = match memory::Image::create(context, mem_flag | alloc_flag, &format, &desc, data) {
+            Ok(image) => image,
+            Err(error) => return Err(format!("error creating image: {}", error)),
+        };
+
+        Ok(image)
+    }
+}
+
+pub fn get_image_region<T>(image: &memory::Image) -> Result<[usize; 3], String> {
+    let element_size = match image.element_size() {
+        Ok(element_size) => element_size,
+        Err(error) => return Err(format!("error querying image element size: {}", error)),
+    };
+
+    if element_size != std::mem::size_of::<T>() {
+        return Err(format!(
+            "image element size is {} bytes, host element size is {} bytes",
+            element_size,
+            std::mem::size_of::<T>()
+        ));
+    }
+
+    let width = match image.width() {
+        Ok(width) => width,
+        Err(error) => return Err(format!("error querying image width: {}", error)),
+    };
+
+    let height = match image.height() {
+        Ok(height) => height,
+        Err(error) => return Err(format!("error querying image height: {}", error)),
+    };
+
+    let depth = match image.depth() {
+        Ok(depth) => depth,
+        Err(error) => return Err(format!("error querying image depth: {}", error)),
+    };
+
+    Ok([width, std::cmp::max(height, 1), std::cmp::max(depth, 1)])
+}
+
+pub fn write_image<T: traits::HasImageFormat>(
+    queue: &command_queue::CommandQueue,
+    image: &mut memory::Image,
+    input: &[T],
+    wait: impl AsRef<[event::Event]>,
+) -> Result<event::Event, String> {
+    let region = match get_image_region::<T>(image) {
+        Ok(region) => region,
+        Err(error) => return Err(error),
+    };
+
+    if region[0] * region[1] * region[2] > input.len() {
+        return Err(format!(
+            "host data of {} elements is too small for image region {:?}",
+            input.len(),
+            region
+        ));
+    }
+
+    let origin = [0usize; 3];
+    let wait_list = events::EventList::from(wait.as_ref());
+
+    unsafe {
+        let event = match command_queue::enqueue_write_image(
+            queue.get(),
+            image.get_mut(),
+            command_queue::CL_NON_BLOCKING,
+            origin.as_ptr(),
+            region.as_ptr(),
+            0,
+            0,
+            input.as_ptr() as *mut c_void,
+            wait_list.count(),
+            wait_list.as_ptr(),
+        ) {
+            Ok(event) => event,
+            Err(error) => return Err(format!("error writing image: {}", error)),
+        };
+
+        let event = event::Event::from(event);
+        let bytes = region[0] * region[1] * region[2] * std::mem::size_of::<T>();
+        trace::record_transfer(queue, &event, "write_image", bytes, &wait_list);
+        Ok(event)
+    }
+}
+
+pub fn read_image<T: traits::HasImageFormat>(
+    queue: &command_queue::CommandQueue,
+    image: &memory::Image,
+    output: &mut [T],
+    wait: impl AsRef<[event::Event]>,
+) -> Result<event::Event, String> {
+    let region = match get_image_region::<T>(image) {
+        Ok(region) => region,
+        Err(error) => return Err(error),
+    };
+
+    if region[0] * region[1] * region[2] > output.len() {
+        return Err(format!(
+            "host data of {} elements is too small for image region {:?}",
+            output.len(),
+            region
+        ));
+    }
+
+    let origin = [0usize; 3];
+    let wait_list = events::EventList::from(wait.as_ref());
+
+    unsafe {
+        let event = match command_queue::enqueue_read_image(
+            queue.get(),
+            image.get(),
+            command_queue::CL_NON_BLOCKING,
+            origin.as_ptr(),
+            region.as_ptr(),
+            0,
+            0,
+            output.as_mut_ptr() as *mut c_void,
+            wait_list.count(),
+            wait_list.as_ptr(),
+        ) {
+            Ok(event) => event,
+            Err(error) => return Err(format!("error reading image: {}", error)),
+        };
+
+        let event = event::Event::from(event);
+        let bytes = region[0] * region[1] * region[2] * std::mem::size_of::<T>();
+        trace::record_transfer(queue, &event, "read_image", bytes, &wait_list);
+        Ok(event)
+    }
+}
@@ -0,0 +1,465 @@
+use opencl3::command_queue;
+use opencl3::context;
+use opencl3::event;
+use opencl3::kernel;
+use opencl3::memory;
+use opencl3::memory::ClMem;
+use opencl3::types::cl_uint;
+
+use crate::clvecadd::buffer;
+use crate::clvecadd::events;
+use crate::clvecadd::exec;
+use crate::clvecadd::registry;
+use crate::clvecadd::traits;
+
+pub trait BoundArg {
+    fn set(&self, kernel: &kernel::Kernel, index: cl_uint) -> Result<(), String>;
+
+    fn elements(&self) -> usize {
+        0
+    }
+
+    fn upload(&self) -> Option<&event::Event> {
+        None
+    }
+
+    fn download(&mut self, _queue: &command_queue::CommandQueue, _kernel_event: &event::Event) -> Result<Option<event::Event>, String> {
+        Ok(None)
+    }
+}
+
+pub trait IntoKernelArg<'a> {
+    // how the argument binds, checked against the kernel before anything is uploaded
+    fn kind() -> registry::ArgKind;
+
+    // elements of a slice argument, checked against the count before anything is uploaded
+    fn length(&self) -> Option<usize> {
+        None
+    }
+
+    fn count(&self) -> Option<u64> {
+        None
+    }
+
+    fn into_arg(
+        self,
+        context: &context::Context,
+        queue: &command_queue::CommandQueue,
+    ) -> Result<Box<dyn BoundArg + 'a>, String>;
+}
+
+struct ScalarArg<T> {
+    value: T,
+}
+
+struct SliceArg<'a, T> {
+    buffer: memory::Buffer<T>,
+    upload: Option<event::Event>,
+    input: &'a [T],
+}
+
+struct MutSliceArg<'a, T> {
+    buffer: memory::Buffer<T>,
+    upload: Option<event::Event>,
+    output: &'a mut [T],
+}
+
+fn create_device_buffer<T: Copy>(
+    context: &context::Context,
+    queue: &command_queue::CommandQueue,
+    input: &[T],
+    mode: buffer::MemMode,
+) -> Result<(memory::Buffer<T>, Option<event::Event>), String> {
+    // OpenCL rejects empty buffers, the kernel still sees a length of zero
+    let buffer = unsafe {
+        match memory::Buffer::<T>::create(
+            context,
+            buffer::get_mem_flag(mode),
+            std::cmp::max(input.len(), 1),
+            std::ptr::null_mut(),
+        ) {
+            Ok(buffer) => buffer,
+            Err(error) => return Err(format!("error creating buffer: {}", error)),
+        }
+    };
+
+    if input.is_empty() {
+        return Ok((buffer, None));
+    }
+
+    match buffer::enqueue_write_buffer(queue, &buffer, input, &events::EventList::new()) {
+        Ok(event) => Ok((buffer, Some(event))),
+        Err(error) => Err(error),
+    }
+}
+
+fn set_mem_arg(kernel: &kernel::Kernel, index: cl_uint, mem: &dyn ClMem) -> Result<(), String> {
+    match unsafe { kernel.set_arg(index, &mem.get()) } {
+        Ok(_) => Ok(()),
+        Err(error) => Err(format!("error setting kernel argument {}: {}", index + 1, error)),
+    }
+}
+
+// an upload still reads from the borrowed slice, so it has to finish first
+impl<'a, T> Drop for SliceArg<'a, T> {
+    fn drop(&mut self) {
+        if let Some(upload) = &self.upload {
+            let _ = upload.wait();
+        }
+    }
+}
+
+impl<'a, T> Drop for MutSliceArg<'a, T> {
+    fn drop(&mut self) {
+        if let Some(upload) = &self.upload {
+            let _ = upload.wait();
+        }
+    }
+}
+
+impl<T: Copy> BoundArg for ScalarArg<T> {
+    fn set(&self, kernel: &kernel::Kernel, index: cl_uint) -> Result<(), String> {
+        match unsafe { kernel.set_arg(index, &self.value) } {
+            Ok(_) => Ok(()),
+            Err(error) => Err(format!("error setting kernel argument {}: {}", index + 1, error)),
+        }
+    }
+}
+
+impl<'a, T: Copy> BoundArg for SliceArg<'a, T> {
+    fn set(&self, kernel: &kernel::Kernel, index: cl_uint) -> Result<(), String> {
+        set_mem_arg(kernel, index, &self.buffer)
+    }
+
+    fn elements(&self) -> usize {
+        self.input.len()
+    }
+
+    fn upload(&self) -> Option<&event::Event> {
+        self.upload.as_ref()
+    }
+}
+
+impl<'a, T: Copy> BoundArg for MutSliceArg<'a, T> {
+    fn set(&self, kernel: &kernel::Kernel, index: cl_uint) -> Result<(), String> {
+        set_mem_arg(kernel, index, &self.buffer)
+    }
+
+    fn elements(&self) -> usize {
+        self.output.len()
+    }
+
+    fn upload(&self) -> Option<&event::Event> {
+        self.upload.as_ref()
+    }
+
+    fn download(&mut self, queue: &command_queue::CommandQueue, kernel_event: &event::Event) -> Result<Option<event::Event>, String> {
+        if self.output.is_empty() {
+            return Ok(None);
+        }
+
+        let mut wait_list = events::EventList::new();
+        wait_list.push(kernel_event);
+        match buffer::enqueue_read_buffer(queue, &self.buffer, self.output, &wait_list) {
+            Ok(event) => Ok(Some(event)),
+            Err(error) => Err(error),
+        }
+    }
+}
+
+impl<'a, T: traits::OpenclNum + Copy + 'a> IntoKernelArg<'a> for T {
+    fn kind() -> registry::ArgKind {
+        registry::ArgKind::Scalar
+    }
+
+    fn count(&self) -> Option<u64> {
+        self.as_count()
+    }
+
+    fn into_arg(
+        self,
+        _context: &context::Context,
+        _queue: &command_queue::CommandQueue,
+    ) -> Result<Box<dyn BoundArg + 'a>, String> {
+        Ok(Box::new(ScalarArg { value: self }))
+    }
+}
+
+impl<'a, T: traits::OpenclNum + Copy + 'a> IntoKernelArg<'a> for &'a [T] {
+    fn kind() -> registry::ArgKind {
+        registry::ArgKind::Input
+    }
+
+    fn length(&self) -> Option<usize> {
+        Some(self.len())
+    }
+
+    fn into_arg(
+        self,
+        context: &context::Context,
+        queue: &command_queue::CommandQueue,
+    ) -> Result<Box<dyn BoundArg + 'a>, String> {
+        match create_device_buffer(context, queue, self, buffer::MemMode::Read) {
+            Ok((buffer, upload)) => Ok(Box::new(SliceArg {
+                buffer,
+                upload,
+                input: self,
+            })),
+            Err(error) => Err(error),
+        }
+    }
+}
+
+// the current contents are uploaded too, so in-place kernels see their input
+impl<'a, T: traits::OpenclNum + Copy + 'a> IntoKernelArg<'a> for &'a mut [T] {
+    fn kind() -> registry::ArgKind {
+        registry::ArgKind::InOut
+    }
+
+    fn length(&self) -> Option<usize> {
+        Some(self.len())
+    }
+
+    fn into_arg(
+        self,
+        context: &context::Context,
+        queue: &command_queue::CommandQueue,
+    ) -> Result<Box<dyn BoundArg + 'a>, String> {
+        match create_device_buffer(context, queue, self, buffer::MemMode::ReadWrite) {
+            Ok((buffer, upload)) => Ok(Box::new(MutSliceArg {
+                buffer,
+                upload,
+                output: self,
+            })),
+            Err(error) => Err(error),
+        }
+    }
+}
+
+fn accepts(expected: registry::ArgKind, given: registry::ArgKind) -> bool {
+    use registry::ArgKind;
+    match given {
+        ArgKind::Scalar | ArgKind::Count => matches!(expected, ArgKind::Scalar | ArgKind::Count),
+        ArgKind::Input | ArgKind::MaskInput => matches!(expected, ArgKind::Input | ArgKind::MaskInput),
+        ArgKind::Output | ArgKind::InOut | ArgKind::MaskOutput | ArgKind::Flag => {
+            matches!(expected, ArgKind::Output | ArgKind::InOut | ArgKind::MaskOutput | ArgKind::Flag)
+        }
+    }
+}
+
+pub fn check_args(handle: registry::Kernels, given: &[registry::ArgKind]) -> Result<(), String> {
+    let info = handle.info();
+    if given.len() != info.args.len() {
+        return Err(format!(
+            "{} takes {} arguments, {} declared",
+            info.entry_point,
+            info.args.len(),
+            given.len()
+        ));
+    }
+
+    for (index, (expected, given)) in info.args.iter().zip(given).enumerate() {
+        if !accepts(*expected, *given) {
+            return Err(format!(
+                "argument {} of {} is {:?}, {:?} declared",
+                index + 1,
+                info.entry_point,
+                expected,
+                given
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+// the device buffers are sized to their slices, so every element slice has to
+// be as long as the others and the count must not run past them
+pub fn check_lengths(handle: registry::Kernels, lengths: &[Option<usize>], counts: &[Option<u64>]) -> Result<(), String> {
+    use registry::ArgKind;
+    let info = handle.info();
+
+    let mut elements: Option<usize> = None;
+    for (index, kind) in info.args.iter().enumerate() {
+        let length = lengths.get(index).copied().flatten();
+        match kind {
+            ArgKind::Input | ArgKind::Output | ArgKind::InOut | ArgKind::MaskInput | ArgKind::MaskOutput => {
+                match (elements, length) {
+                    (_, None) => return Err(format!("argument {} of {} is not a slice", index + 1, info.entry_point)),
+                    (None, Some(length)) => elements = Some(length),
+                    (Some(expected), Some(length)) if expected != length => {
+                        return Err(format!(
+                            "argument {} of {} has {} elements, earlier slices have {}",
+                            index + 1,
+                            info.entry_point,
+                            length,
+                            expected
+                        ));
+                    }
+                    _ => (),
+                };
+            }
+            ArgKind::Flag if length.unwrap_or(0) == 0 => {
+                return Err(format!("argument {} of {} must hold at least one element", index + 1, info.entry_point));
+            }
+            _ => (),
+        };
+    }
+
+    for (index, kind) in info.args.iter().enumerate() {
+        if *kind != ArgKind::Count {
+            continue;
+        }
+
+        let count = match counts.get(index).copied().flatten() {
+            Some(count) => count,
+            None => return Err(format!("argument {} of {} must be a ulong count", index + 1, info.entry_point)),
+        };
+        let available = elements.unwrap_or(0);
+        if count > available as u64 {
+            return Err(format!(
+                "{} elements requested from {} but the slices hold {}",
+                count, info.entry_point, available
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+// binds the arguments in declaration order, launches over the longest slice and
+// blocks until every mutable slice has been read back
+pub fn launch(
+    queue: &command_queue::CommandQueue,
+    kernel: &kernel::Kernel,
+    args: &mut [Box<dyn BoundArg + '_>],
+) -> Result<(), String> {
+    let mut elements = 0;
+    for (index, arg) in args.iter().enumerate() {
+        match arg.set(kernel, index as cl_uint) {
+            Ok(_) => (),
+            Err(error) => return Err(error),
+        };
+        elements = std::cmp::max(elements, arg.elements());
+    }
+
+    let kernel_event = {
+        let mut wait_list = events::EventList::new();
+        for arg in args.iter() {
+            if let Some(upload) = arg.upload() {
+                wait_list.push(upload);
+            }
+        }
+
+        match exec::enqueue_kernel(queue, kernel, elements, exec::LocalSize::Auto, &wait_list) {
+            Ok(kernel_event) => kernel_event,
+            Err(error) => return Err(error),
+        }
+    };
+
+    let mut downloads = Vec::new();
+    for arg in args.iter_mut() {
+        match arg.download(queue, &kernel_event) {
+            Ok(Some(download)) => downloads.push(download),
+            Ok(None) => (),
+            Err(error) => {
+                // earlier downloads still write into borrowed slices
+                let _ = queue.finish();
+                return Err(error);
+            }
+        };
+    }
+
+    match kernel_event.wait() {
+        Ok(_) => (),
+        Err(error) => {
+            let _ = queue.finish();
+            return Err(format!("error waiting for kernel: {}", error));
+        }
+    };
+
+    // every download writes into a borrowed slice, so all of them are waited for
+    let mut result = Ok(());
+    for download in downloads {
+        match download.wait() {
+            Ok(_) => (),
+            Err(error) => {
+                if result.is_ok() {
+                    result = Err(format!("error reading back buffer: {}", error));
+                }
+            }
+        };
+    }
+
+    result
+}
+
+// looks up the registered kernel for T, binds the arguments in order and
+// blocks until it has run, shared by the arith, math and mask wrappers
+pub(crate) fn run_kernel<'a, T: traits::OpenclNum + traits::HasOpenclString>(
+    context: &context::Context,
+    queue: &command_queue::CommandQueue,
+    handle: registry::Kernels,
+    args: Vec<Result<Box<dyn BoundArg + 'a>, String>>,
+) -> Result<(), String> {
+    let kernel = match registry::get_kernel::<T>(context, handle) {
+        Ok(kernel) => kernel,
+        Err(error) => return Err(error),
+    };
+
+    let mut bound: Vec<Box<dyn BoundArg + 'a>> = Vec::new();
+    for arg in args {
+        match arg {
+            Ok(arg) => bound.push(arg),
+            Err(error) => return Err(error),
+        };
+    }
+
+    launch(queue, &kernel, &mut bound)
+}
+
+// declares a launcher for a registered kernel, for example
+//   cl_kernel!(pub fn add_vectors<T>(a: &[T], b: &[T], c: &mut [T], n: u64) = AddVectors);
+// generates add_vectors(context, queue, a, b, c, n) -> Result<(), String>, an unknown
+// Kernels variant does not compile and the argument kinds, slice lengths and count
+// are checked before any upload
+#[macro_export]
+macro_rules! cl_kernel {
+    ($vis:vis fn $name:ident<$t:ident>($($arg:ident : $ty:ty),* $(,)?) = $handle:ident) => {
+        #[allow(clippy::too_many_arguments)]
+        $vis fn $name<$t: Copy + $crate::clvecadd::traits::OpenclNum + $crate::clvecadd::traits::HasOpenclString>(
+            context: &opencl3::context::Context,
+            queue: &opencl3::command_queue::CommandQueue,
+            $($arg: $ty),*
+        ) -> Result<(), String> {
+            let handle = $crate::clvecadd::registry::Kernels::$handle;
+            let kinds = [$(<$ty as $crate::clvecadd::launch::IntoKernelArg<'_>>::kind()),*];
+            match $crate::clvecadd::launch::check_args(handle, &kinds) {
+                Ok(_) => (),
+                Err(error) => return Err(error),
+            };
+
+            let lengths = [$(<$ty as $crate::clvecadd::launch::IntoKernelArg<'_>>::length(&$arg)),*];
+            let counts = [$(<$ty as $crate::clvecadd::launch::IntoKernelArg<'_>>::count(&$arg)),*];
+            match $crate::clvecadd::launch::check_lengths(handle, &lengths, &counts) {
+                Ok(_) => (),
+                Err(error) => return Err(error),
+            };
+
+            let kernel = match $crate::clvecadd::registry::get_kernel::<$t>(context, handle) {
+                Ok(kernel) => kernel,
+                Err(error) => return Err(error),
+            };
+
+            let mut args: Vec<Box<dyn $crate::clvecadd::launch::BoundArg + '_>> = Vec::new();
+            $(
+                match $crate::clvecadd::launch::IntoKernelArg::into_arg($arg, context, queue) {
+                    Ok(arg) => args.push(arg),
+                    Err(error) => return Err(error),
+                };
+            )*
+
+            $crate::clvecadd::launch::launch(queue, &kernel, &mut args)
+        }
+    };
+}
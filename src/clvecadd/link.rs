@@ -0,0 +1,256 @@
+use opencl3::context;
+use opencl3::kernel;
+use opencl3::program;
+use opencl3::types::{cl_int, cl_program, cl_uint};
+use opencl_sys::clLinkProgram;
+use log::warn;
+use std::collections::BTreeMap;
+use std::ffi::{CStr, CString};
+use std::fs;
+use std::path::Path;
+
+use crate::clvecadd::buildlog;
+
+const EMBEDDED_HEADERS: [(&str, &str); 1] = [("clvecadd.h", include_str!("../opencl/include/clvecadd.h"))];
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HeaderMap {
+    headers: BTreeMap<String, String>,
+}
+
+impl HeaderMap {
+    pub fn new() -> HeaderMap {
+        HeaderMap {
+            headers: BTreeMap::new(),
+        }
+    }
+
+    // the headers shipped in src/opencl/include, resolvable as #include "<name>"
+    pub fn embedded() -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, content) in EMBEDDED_HEADERS {
+            headers.insert(name, content);
+        }
+        headers
+    }
+
+    pub fn insert(&mut self, name: &str, content: &str) {
+        self.headers.insert(name.to_string(), content.to_string());
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).map(|content| content.as_str())
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.headers.keys().map(|name| name.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.headers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.headers.is_empty()
+    }
+}
+
+pub fn compile_source(
+    context: &context::Context,
+    name: &str,
+    content: &str,
+    headers: &HeaderMap,
+    options: &str,
+) -> Result<program::Program, String> {
+    let mut header_programs: Vec<program::Program> = Vec::new();
+    let mut include_names: Vec<CString> = Vec::new();
+    for (header, header_content) in &headers.headers {
+        match program::Program::create_from_source(context, header_content) {
+            Ok(header_program) => header_programs.push(header_program),
+            Err(error) => return Err(format!("error creating header {}: {}", header, error)),
+        };
+        match CString::new(header.as_str()) {
+            Ok(include_name) => include_names.push(include_name),
+            Err(error) => return Err(format!("invalid header name {}: {}", header, error)),
+        };
+    }
+    let input_headers: Vec<cl_program> = header_programs.iter().map(|header| header.get()).collect();
+    let header_include_names: Vec<&CStr> = include_names.iter().map(|include_name| include_name.as_c_str()).collect();
+
+    let mut prog = match program::Program::create_from_source(context, content) {
+        Ok(prog) => prog,
+        Err(error) => return Err(format!("error creating program from {}: {}", name, error)),
+    };
+
+    let compiled = prog.compile(context.devices(), options, &input_headers, &header_include_names);
+    let logs = buildlog::get_build_logs(&prog, context).unwrap_or_default();
+
+    match compiled {
+        Ok(_) => (),
+        Err(error) => {
+            return Err(buildlog::BuildFailure {
+                source: name.to_string(),
+                error: error.to_string(),
+                logs,
+            }
+            .into())
+        }
+    };
+
+    for log in &logs {
+        for warning in log.warnings() {
+            warn!("{}: {}", log.device, warning);
+        }
+    }
+
+    Ok(prog)
+}
+
+// Program::link in opencl3 0.9 hands its own program to clLinkProgram as the context
+// and Program::new is private, so the linked program is owned here instead
+#[derive(Debug)]
+pub struct LinkedProgram {
+    program: cl_program,
+    kernel_names: String,
+}
+
+impl LinkedProgram {
+    pub fn get(&self) -> cl_program {
+        self.program
+    }
+
+    pub fn kernel_names(&self) -> &str {
+        &self.kernel_names
+    }
+
+    pub fn create_kernel(&self, name: &str) -> Result<kernel::Kernel, String> {
+        let entry_point = match CString::new(name) {
+            Ok(entry_point) => entry_point,
+            Err(error) => return Err(format!("invalid kernel name {}: {}", name, error)),
+        };
+
+        match kernel::create_kernel(self.program, &entry_point) {
+            Ok(created) => Ok(kernel::Kernel::new(created)),
+            Err(error) => Err(format!("not able to get kernel {}: {}", name, error)),
+        }
+    }
+}
+
+impl Drop for LinkedProgram {
+    fn drop(&mut self) {
+        let _ = unsafe { program::release_program(self.program) };
+    }
+}
+
+unsafe impl Send for LinkedProgram {}
+unsafe impl Sync for LinkedProgram {}
+
+pub fn link_programs(
+    context: &context::Context,
+    programs: Vec<program::Program>,
+    options: &str,
+) -> Result<LinkedProgram, String> {
+    if programs.is_empty() {
+        return Err(String::from("no programs to link"));
+    }
+
+    let input_programs: Vec<cl_program> = programs.iter().map(|prog| prog.get()).collect();
+    let link_options = match CString::new(options) {
+        Ok(link_options) => link_options,
+        Err(error) => return Err(format!("invalid link options {}: {}", options, error)),
+    };
+
+    // clLinkProgram may hand back a program on failure too, it holds the link log
+    let mut status: cl_int = 0;
+    let linked = unsafe {
+        clLinkProgram(
+            context.get(),
+            context.devices().len() as cl_uint,
+            context.devices().as_ptr(),
+            link_options.as_ptr(),
+            input_programs.len() as cl_uint,
+            input_programs.as_ptr(),
+            None,
+            std::ptr::null_mut(),
+            &mut status,
+        )
+    };
+
+    let logs = if linked.is_null() {
+        Vec::new()
+    } else {
+        buildlog::get_program_build_logs(linked, context).unwrap_or_default()
+    };
+
+    if status != 0 || linked.is_null() {
+        if !linked.is_null() {
+            let _ = unsafe { program::release_program(linked) };
+        }
+        return Err(buildlog::BuildFailure {
+            source: String::from("link"),
+            error: format!("error linking programs: {}", opencl3::error_codes::ClError(status)),
+            logs,
+        }
+        .into());
+    }
+
+    for log in &logs {
+        for warning in log.warnings() {
+            warn!("{}: {}", log.device, warning);
+        }
+    }
+
+    let kernel_names = match program::get_program_info(linked, program::CL_PROGRAM_KERNEL_NAMES) {
+        Ok(kernel_names) => String::from(kernel_names).trim_end_matches('\0').to_string(),
+        Err(error) => {
+            let _ = unsafe { program::release_program(linked) };
+            return Err(format!("not able to get kernel names: {}", error));
+        }
+    };
+
+    Ok(LinkedProgram {
+        program: linked,
+        kernel_names,
+    })
+}
+
+pub fn compile_and_link(
+    context: &context::Context,
+    sources: &[(&str, &str)],
+    headers: &HeaderMap,
+    compile_options: &str,
+    link_options: &str,
+) -> Result<LinkedProgram, String> {
+    let mut compiled: Vec<program::Program> = Vec::new();
+    for (name, content) in sources {
+        match compile_source(context, name, content, headers, compile_options) {
+            Ok(prog) => compiled.push(prog),
+            Err(error) => return Err(error),
+        };
+    }
+
+    link_programs(context, compiled, link_options)
+}
+
+pub fn compile_and_link_files(
+    context: &context::Context,
+    sources: &[&Path],
+    headers: &HeaderMap,
+    compile_options: &str,
+    link_options: &str,
+) -> Result<LinkedProgram, String> {
+    let mut contents: Vec<(String, String)> = Vec::new();
+    for source in sources {
+        let content = match fs::read_to_string(source) {
+            Ok(content) => content,
+            Err(error) => return Err(format!("error reading source file {}: {}", source.display(), error)),
+        };
+        contents.push((source.display().to_string(), content));
+    }
+
+    let units: Vec<(&str, &str)> = contents
+        .iter()
+        .map(|(name, content)| (name.as_str(), content.as_str()))
+        .collect();
+    compile_and_link(context, &units, headers, compile_options, link_options)
+}
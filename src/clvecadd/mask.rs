@@ -0,0 +1,111 @@
+use opencl3::command_queue;
+use opencl3::context;
+
+use crate::clvecadd::launch;
+use crate::clvecadd::registry;
+use crate::clvecadd::traits;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Comparison {
+    Eq,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+}
+
+impl Comparison {
+    pub const ALL: [Comparison; 5] = [Comparison::Eq, Comparison::Lt, Comparison::Gt, Comparison::Le, Comparison::Ge];
+
+    pub fn kernel(&self) -> registry::Kernels {
+        match self {
+            Comparison::Eq => registry::Kernels::EqVectors,
+            Comparison::Lt => registry::Kernels::LtVectors,
+            Comparison::Gt => registry::Kernels::GtVectors,
+            Comparison::Le => registry::Kernels::LeVectors,
+            Comparison::Ge => registry::Kernels::GeVectors,
+        }
+    }
+
+    // like OpenCL C every comparison involving NaN is false
+    pub fn apply<T: PartialOrd>(&self, a: &T, b: &T) -> bool {
+        match self {
+            Comparison::Eq => a == b,
+            Comparison::Lt => a < b,
+            Comparison::Gt => a > b,
+            Comparison::Le => a <= b,
+            Comparison::Ge => a >= b,
+        }
+    }
+}
+
+pub fn to_bools(mask: &[u8]) -> Vec<bool> {
+    mask.iter().map(|value| *value != 0).collect()
+}
+
+pub fn from_bools(values: &[bool]) -> Vec<u8> {
+    values.iter().map(|value| *value as u8).collect()
+}
+
+// keeps the elements whose mask entry is set
+pub fn filter<T: Copy>(mask: &[u8], a: &[T]) -> Vec<T> {
+    a.iter().zip(mask).filter(|(_, selected)| **selected != 0).map(|(value, _)| *value).collect()
+}
+
+pub fn compare_cpu<T: PartialOrd>(cmp: Comparison, a: &[T], b: &[T]) -> Result<Vec<u8>, String> {
+    Ok(a.iter().zip(b).map(|(a, b)| cmp.apply(a, b) as u8).collect())
+}
+
+pub fn select_cpu<T: Copy>(mask: &[u8], a: &[T], b: &[T]) -> Result<Vec<T>, String> {
+    let size = std::cmp::min(mask.len(), std::cmp::min(a.len(), b.len()));
+    Ok((0..size).map(|i| if mask[i] != 0 { a[i] } else { b[i] }).collect())
+}
+
+pub fn compare<T: Copy + traits::OpenclNum + traits::HasOpenclString>(
+    context: &context::Context,
+    queue: &command_queue::CommandQueue,
+    cmp: Comparison,
+    a: &[T],
+    b: &[T],
+) -> Result<Vec<u8>, String> {
+    let size = std::cmp::min(a.len(), b.len());
+    let mut mask: Vec<u8> = vec![0; size];
+    let args = vec![
+        launch::IntoKernelArg::into_arg(&a[..size], context, queue),
+        launch::IntoKernelArg::into_arg(&b[..size], context, queue),
+        launch::IntoKernelArg::into_arg(&mut mask[..], context, queue),
+        launch::IntoKernelArg::into_arg(size as u64, context, queue),
+    ];
+
+    match launch::run_kernel::<T>(context, queue, cmp.kernel(), args) {
+        Ok(_) => (),
+        Err(error) => return Err(error),
+    };
+
+    Ok(mask)
+}
+
+pub fn select<T: Default + Copy + traits::OpenclNum + traits::HasOpenclString>(
+    context: &context::Context,
+    queue: &command_queue::CommandQueue,
+    mask: &[u8],
+    a: &[T],
+    b: &[T],
+) -> Result<Vec<T>, String> {
+    let size = std::cmp::min(mask.len(), std::cmp::min(a.len(), b.len()));
+    let mut c: Vec<T> = vec![<T>::default(); size];
+    let args = vec![
+        launch::IntoKernelArg::into_arg(&mask[..size], context, queue),
+        launch::IntoKernelArg::into_arg(&a[..size], context, queue),
+        launch::IntoKernelArg::into_arg(&b[..size], context, queue),
+        launch::IntoKernelArg::into_arg(&mut c[..], context, queue),
+        launch::IntoKernelArg::into_arg(size as u64, context, queue),
+    ];
+
+    match launch::run_kernel::<T>(context, queue, registry::Kernels::SelectVectors, args) {
+        Ok(_) => (),
+        Err(error) => return Err(error),
+    };
+
+    Ok(c)
+}
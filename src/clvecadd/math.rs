@@ -0,0 +1,247 @@
+use half::f16;
+use num_traits::Float;
+use opencl3::command_queue;
+use opencl3::context;
+
+use crate::clvecadd::launch;
+use crate::clvecadd::registry;
+use crate::clvecadd::traits;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MathFn {
+    Sqrt,
+    Exp,
+    Log,
+    Sin,
+    Pow,
+}
+
+impl MathFn {
+    pub const ALL: [MathFn; 5] = [MathFn::Sqrt, MathFn::Exp, MathFn::Log, MathFn::Sin, MathFn::Pow];
+
+    pub fn kernel(&self) -> registry::Kernels {
+        match self {
+            MathFn::Sqrt => registry::Kernels::SqrtVector,
+            MathFn::Exp => registry::Kernels::ExpVector,
+            MathFn::Log => registry::Kernels::LogVector,
+            MathFn::Sin => registry::Kernels::SinVector,
+            MathFn::Pow => registry::Kernels::PowVectors,
+        }
+    }
+
+    pub fn arity(&self) -> usize {
+        match self {
+            MathFn::Pow => 2,
+            _ => 1,
+        }
+    }
+
+    // error bound the OpenCL specification allows for the built-in on T
+    pub fn max_ulp<T: MathNum>(&self) -> u64 {
+        <T>::max_ulp(*self)
+    }
+}
+
+// cpu reference through libm and the distance in units in the last place
+pub trait MathNum: Copy {
+    fn apply_math(f: MathFn, a: Self, b: Self) -> Self;
+    fn ulp_distance(a: Self, b: Self) -> u64;
+    fn max_ulp(f: MathFn) -> u64;
+}
+
+fn apply_float<F: Float>(f: MathFn, a: F, b: F) -> F {
+    match f {
+        MathFn::Sqrt => Float::sqrt(a),
+        MathFn::Exp => Float::exp(a),
+        MathFn::Log => Float::ln(a),
+        MathFn::Sin => Float::sin(a),
+        MathFn::Pow => Float::powf(a, b),
+    }
+}
+
+// maps the sign-magnitude bit patterns onto a line where neighbouring floats
+// are one apart, so -0.0 and 0.0 coincide
+macro_rules! ulp_distance {
+    ($a:expr, $b:expr, $signed:ty) => {{
+        let order = |bits: $signed| if bits < 0 { <$signed>::MIN.wrapping_sub(bits) } else { bits };
+        if $a.is_nan() && $b.is_nan() {
+            0
+        } else if $a.is_nan() || $b.is_nan() {
+            u64::MAX
+        } else {
+            (order($a.to_bits() as $signed) as i128 - order($b.to_bits() as $signed) as i128).unsigned_abs() as u64
+        }
+    }};
+}
+
+impl MathNum for f32 {
+    fn apply_math(f: MathFn, a: f32, b: f32) -> f32 {
+        apply_float(f, a, b)
+    }
+
+    fn ulp_distance(a: f32, b: f32) -> u64 {
+        ulp_distance!(a, b, i32)
+    }
+
+    fn max_ulp(f: MathFn) -> u64 {
+        match f {
+            MathFn::Sqrt | MathFn::Exp | MathFn::Log => 3,
+            MathFn::Sin => 4,
+            MathFn::Pow => 16,
+        }
+    }
+}
+
+impl MathNum for f64 {
+    fn apply_math(f: MathFn, a: f64, b: f64) -> f64 {
+        apply_float(f, a, b)
+    }
+
+    fn ulp_distance(a: f64, b: f64) -> u64 {
+        ulp_distance!(a, b, i64)
+    }
+
+    // double precision sqrt has to be correctly rounded
+    fn max_ulp(f: MathFn) -> u64 {
+        match f {
+            MathFn::Sqrt => 0,
+            MathFn::Exp | MathFn::Log => 3,
+            MathFn::Sin => 4,
+            MathFn::Pow => 16,
+        }
+    }
+}
+
+// evaluated in double precision and rounded to half once
+impl MathNum for f16 {
+    fn apply_math(f: MathFn, a: f16, b: f16) -> f16 {
+        f16::from_f64(apply_float(f, a.to_f64(), b.to_f64()))
+    }
+
+    fn ulp_distance(a: f16, b: f16) -> u64 {
+        ulp_distance!(a, b, i16)
+    }
+
+    // "ULP values for half precision built-in math functions" in the OpenCL C
+    // specification, sqrt allows 1.5 ulp and fractional limits are rounded up
+    fn max_ulp(f: MathFn) -> u64 {
+        match f {
+            MathFn::Sqrt => 2,
+            MathFn::Exp | MathFn::Log | MathFn::Sin => 2,
+            MathFn::Pow => 4,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct UlpReport {
+    pub max_ulp: u64,
+    pub index: usize,
+    pub elements: usize,
+}
+
+fn check_arity<T>(f: MathFn, args: &[&[T]]) -> Result<usize, String> {
+    if args.len() != f.arity() {
+        return Err(format!("{:?} takes {} arguments, {} given", f, f.arity(), args.len()));
+    }
+
+    match args.iter().map(|arg| arg.len()).min() {
+        Some(size) => Ok(size),
+        None => Ok(0),
+    }
+}
+
+pub fn math_op_cpu<T: MathNum>(f: MathFn, args: &[&[T]]) -> Result<Vec<T>, String> {
+    let size = match check_arity(f, args) {
+        Ok(size) => size,
+        Err(error) => return Err(error),
+    };
+
+    // unary functions ignore the second operand
+    let second = args[args.len() - 1];
+    Ok((0..size).map(|i| <T>::apply_math(f, args[0][i], second[i])).collect())
+}
+
+pub fn math_op<T: Default + Copy + traits::OpenclNum + traits::HasOpenclString>(
+    context: &context::Context,
+    queue: &command_queue::CommandQueue,
+    f: MathFn,
+    args: &[&[T]],
+) -> Result<Vec<T>, String> {
+    let size = match check_arity(f, args) {
+        Ok(size) => size,
+        Err(error) => return Err(error),
+    };
+
+    let mut c: Vec<T> = vec![<T>::default(); size];
+    let mut bound = Vec::new();
+    for arg in args {
+        bound.push(launch::IntoKernelArg::into_arg(&arg[..size], context, queue));
+    }
+    bound.push(launch::IntoKernelArg::into_arg(&mut c[..], context, queue));
+    bound.push(launch::IntoKernelArg::into_arg(size as u64, context, queue));
+
+    match launch::run_kernel::<T>(context, queue, f.kernel(), bound) {
+        Ok(_) => (),
+        Err(error) => return Err(error),
+    };
+
+    Ok(c)
+}
+
+pub fn compare_ulp<T: MathNum>(device: &[T], reference: &[T]) -> Result<UlpReport, String> {
+    if device.len() != reference.len() {
+        return Err(format!("{} results compared against {} reference values", device.len(), reference.len()));
+    }
+
+    let mut report = UlpReport {
+        elements: device.len(),
+        ..UlpReport::default()
+    };
+    for (index, (value, expected)) in device.iter().zip(reference).enumerate() {
+        let distance = <T>::ulp_distance(*value, *expected);
+        if distance > report.max_ulp {
+            report.max_ulp = distance;
+            report.index = index;
+        }
+    }
+
+    Ok(report)
+}
+
+// runs f on the device and fails when any element is further than max_ulp from the libm reference
+pub fn verify_math_op<T: Default + Copy + MathNum + traits::OpenclNum + traits::HasOpenclString>(
+    context: &context::Context,
+    queue: &command_queue::CommandQueue,
+    f: MathFn,
+    args: &[&[T]],
+    max_ulp: u64,
+) -> Result<UlpReport, String> {
+    let device = match math_op(context, queue, f, args) {
+        Ok(device) => device,
+        Err(error) => return Err(error),
+    };
+
+    let reference = match math_op_cpu(f, args) {
+        Ok(reference) => reference,
+        Err(error) => return Err(error),
+    };
+
+    let report = match compare_ulp(&device, &reference) {
+        Ok(report) => report,
+        Err(error) => return Err(error),
+    };
+
+    if report.max_ulp > max_ulp {
+        return Err(format!(
+            "{:?} on {} is off by {} ulp at element {}, at most {} allowed",
+            f,
+            <T>::as_opencl_string(),
+            report.max_ulp,
+            report.index,
+            max_ulp
+        ));
+    }
+
+    Ok(report)
+}
@@ -0,0 +1,248 @@
+use opencl3::context;
+use opencl3::device;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ClStandard {
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl ClStandard {
+    pub const CL1_1: ClStandard = ClStandard { major: 1, minor: 1 };
+    pub const CL1_2: ClStandard = ClStandard { major: 1, minor: 2 };
+    pub const CL2_0: ClStandard = ClStandard { major: 2, minor: 0 };
+    pub const CL3_0: ClStandard = ClStandard { major: 3, minor: 0 };
+
+    // parses strings such as "OpenCL C 1.2 " or "OpenCL C 3.0 (Build 0)"
+    pub fn parse(version: &str) -> Result<ClStandard, String> {
+        let number = match version.trim().strip_prefix("OpenCL C ") {
+            Some(rest) => rest.split_whitespace().next().unwrap_or(""),
+            None => return Err(format!("not an OpenCL C version: {}", version)),
+        };
+
+        let parsed = match number.split_once('.') {
+            Some((major, minor)) => (major.parse::<u32>(), minor.parse::<u32>()),
+            None => return Err(format!("not an OpenCL C version: {}", version)),
+        };
+
+        match parsed {
+            (Ok(major), Ok(minor)) => Ok(ClStandard { major, minor }),
+            _ => Err(format!("not an OpenCL C version: {}", version)),
+        }
+    }
+}
+
+impl fmt::Display for ClStandard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "CL{}.{}", self.major, self.minor)
+    }
+}
+
+pub fn get_opencl_c_version(device: &device::Device) -> Result<ClStandard, String> {
+    match device.opencl_c_version() {
+        Ok(version) => ClStandard::parse(&version),
+        Err(error) => Err(format!("not able to get OpenCL C version: {}", error)),
+    }
+}
+
+// the highest standard every device of the context can compile
+pub fn detect_standard(context: &context::Context) -> Result<ClStandard, String> {
+    let mut standard: Option<ClStandard> = None;
+    for device_id in context.devices() {
+        let version = match get_opencl_c_version(&device::Device::new(*device_id)) {
+            Ok(version) => version,
+            Err(error) => return Err(error),
+        };
+        standard = match standard {
+            Some(standard) if standard <= version => Some(standard),
+            _ => Some(version),
+        };
+    }
+
+    match standard {
+        Some(standard) => Ok(standard),
+        None => Err(String::from("context has no devices")),
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Warnings {
+    #[default]
+    Default,
+    Suppress,
+    AsErrors,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BuildOptions {
+    standard: Option<ClStandard>,
+    defines: BTreeMap<String, Option<String>>,
+    include_paths: Vec<PathBuf>,
+    warnings: Warnings,
+    opt_disable: bool,
+    mad_enable: bool,
+    no_signed_zeros: bool,
+    unsafe_math_optimizations: bool,
+    finite_math_only: bool,
+    fast_relaxed_math: bool,
+    denorms_are_zero: bool,
+}
+
+impl BuildOptions {
+    pub fn new() -> BuildOptions {
+        BuildOptions::default()
+    }
+
+    pub fn standard(mut self, standard: ClStandard) -> BuildOptions {
+        self.standard = Some(standard);
+        self
+    }
+
+    pub fn detect_standard(self, context: &context::Context) -> Result<BuildOptions, String> {
+        match detect_standard(context) {
+            Ok(standard) => Ok(self.standard(standard)),
+            Err(error) => Err(error),
+        }
+    }
+
+    pub fn define(mut self, name: &str, value: &str) -> BuildOptions {
+        self.defines.insert(name.to_string(), Some(value.to_string()));
+        self
+    }
+
+    pub fn flag(mut self, name: &str) -> BuildOptions {
+        self.defines.insert(name.to_string(), None);
+        self
+    }
+
+    pub fn include_path(mut self, path: &Path) -> BuildOptions {
+        if !self.include_paths.iter().any(|include| include == path) {
+            self.include_paths.push(path.to_path_buf());
+        }
+        self
+    }
+
+    pub fn warnings(mut self, warnings: Warnings) -> BuildOptions {
+        self.warnings = warnings;
+        self
+    }
+
+    pub fn optimize(mut self, enabled: bool) -> BuildOptions {
+        self.opt_disable = !enabled;
+        self
+    }
+
+    pub fn mad_enable(mut self, enabled: bool) -> BuildOptions {
+        self.mad_enable = enabled;
+        self
+    }
+
+    pub fn no_signed_zeros(mut self, enabled: bool) -> BuildOptions {
+        self.no_signed_zeros = enabled;
+        self
+    }
+
+    pub fn unsafe_math_optimizations(mut self, enabled: bool) -> BuildOptions {
+        self.unsafe_math_optimizations = enabled;
+        self
+    }
+
+    pub fn finite_math_only(mut self, enabled: bool) -> BuildOptions {
+        self.finite_math_only = enabled;
+        self
+    }
+
+    pub fn fast_math(mut self, enabled: bool) -> BuildOptions {
+        self.fast_relaxed_math = enabled;
+        self
+    }
+
+    pub fn denorms_are_zero(mut self, enabled: bool) -> BuildOptions {
+        self.denorms_are_zero = enabled;
+        self
+    }
+
+    pub fn get_standard(&self) -> Option<ClStandard> {
+        self.standard
+    }
+
+    pub fn get_define(&self, name: &str) -> Option<&str> {
+        match self.defines.get(name) {
+            Some(Some(value)) => Some(value.as_str()),
+            _ => None,
+        }
+    }
+
+    pub fn cache_key(&self) -> String {
+        format!("{:016x}", get_stable_hash(self.to_string().as_bytes()))
+    }
+}
+
+// FNV-1a, stable across runs and toolchains unlike DefaultHasher
+pub fn get_stable_hash(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+fn quote(argument: &str) -> String {
+    if argument.contains(char::is_whitespace) || argument.contains('"') {
+        format!("\"{}\"", argument.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        argument.to_string()
+    }
+}
+
+// every setting is written in a fixed order so equal options give equal strings
+impl fmt::Display for BuildOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut arguments: Vec<String> = Vec::new();
+
+        // -cl-std was introduced with OpenCL C 1.1
+        if let Some(standard) = self.standard {
+            if standard >= ClStandard::CL1_1 {
+                arguments.push(format!("-cl-std={}", standard));
+            }
+        }
+
+        let flags = [
+            (self.opt_disable, "-cl-opt-disable"),
+            (self.mad_enable, "-cl-mad-enable"),
+            (self.no_signed_zeros, "-cl-no-signed-zeros"),
+            (self.unsafe_math_optimizations, "-cl-unsafe-math-optimizations"),
+            (self.finite_math_only, "-cl-finite-math-only"),
+            (self.fast_relaxed_math, "-cl-fast-relaxed-math"),
+            (self.denorms_are_zero, "-cl-denorms-are-zero"),
+        ];
+        for (enabled, flag) in flags {
+            if enabled {
+                arguments.push(flag.to_string());
+            }
+        }
+
+        match self.warnings {
+            Warnings::Default => (),
+            Warnings::Suppress => arguments.push(String::from("-w")),
+            Warnings::AsErrors => arguments.push(String::from("-Werror")),
+        };
+
+        for path in &self.include_paths {
+            arguments.push(format!("-I {}", quote(&path.to_string_lossy())));
+        }
+
+        for (name, value) in &self.defines {
+            match value {
+                Some(value) => arguments.push(format!("-D {}={}", name, quote(value))),
+                None => arguments.push(format!("-D {}", name)),
+            };
+        }
+
+        write!(f, "{}", arguments.join(" "))
+    }
+}
@@ -0,0 +1,208 @@
+use opencl3::context;
+use opencl3::memory;
+use opencl3::memory::ClMem;
+use opencl3::types::cl_mem_flags;
+use std::collections::HashMap;
+use std::mem::ManuallyDrop;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex};
+
+use crate::clvecadd::buffer;
+
+const MIN_SIZE_CLASS: usize = 256;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct PoolKey {
+    context: usize,
+    size_class: usize,
+    flags: cl_mem_flags,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub releases: u64,
+    pub bytes_held: usize,
+    pub bytes_in_use: usize,
+}
+
+struct PoolState {
+    free: HashMap<PoolKey, Vec<memory::Buffer<u8>>>,
+    stats: PoolStats,
+    cap: usize,
+}
+
+pub struct BufferPool {
+    state: Arc<Mutex<PoolState>>,
+}
+
+pub struct PooledBuffer<T> {
+    buffer: Option<memory::Buffer<T>>,
+    key: PoolKey,
+    pool: Arc<Mutex<PoolState>>,
+}
+
+pub fn get_size_class(bytes: usize) -> usize {
+    std::cmp::max(bytes, MIN_SIZE_CLASS).next_power_of_two()
+}
+
+fn retype<T, U>(buffer: memory::Buffer<T>) -> memory::Buffer<U> {
+    let buffer = ManuallyDrop::new(buffer);
+    memory::Buffer::new(buffer.get())
+}
+
+impl BufferPool {
+    pub fn new(cap: usize) -> BufferPool {
+        BufferPool {
+            state: Arc::new(Mutex::new(PoolState {
+                free: HashMap::new(),
+                stats: PoolStats::default(),
+                cap,
+            })),
+        }
+    }
+
+    pub fn acquire<T>(
+        &self,
+        context: &context::Context,
+        elements: usize,
+        mode: buffer::MemMode,
+    ) -> Result<PooledBuffer<T>, String> {
+        let size_class = get_size_class(elements * std::mem::size_of::<T>());
+        let key = PoolKey {
+            context: context.get() as usize,
+            size_class,
+            flags: buffer::get_mem_flag(mode),
+        };
+
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(error) => return Err(format!("buffer pool lock poisoned: {}", error)),
+        };
+
+        let reused = match state.free.get_mut(&key) {
+            Some(free) => free.pop(),
+            None => None,
+        };
+
+        let buffer: memory::Buffer<T> = match reused {
+            Some(buffer) => {
+                state.stats.hits += 1;
+                state.stats.bytes_held -= size_class;
+                retype(buffer)
+            }
+            None => {
+                state.stats.misses += 1;
+                unsafe {
+                    match memory::Buffer::<u8>::create(
+                        context,
+                        key.flags,
+                        size_class,
+                        std::ptr::null_mut(),
+                    ) {
+                        Ok(buffer) => retype(buffer),
+                        Err(error) => return Err(format!("error creating buffer: {}", error)),
+                    }
+                }
+            }
+        };
+        state.stats.bytes_in_use += size_class;
+
+        Ok(PooledBuffer {
+            buffer: Some(buffer),
+            key,
+            pool: Arc::clone(&self.state),
+        })
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        match self.state.lock() {
+            Ok(state) => state.stats,
+            Err(error) => error.into_inner().stats,
+        }
+    }
+
+    pub fn cap(&self) -> usize {
+        match self.state.lock() {
+            Ok(state) => state.cap,
+            Err(error) => error.into_inner().cap,
+        }
+    }
+
+    pub fn set_cap(&self, cap: usize) {
+        if let Ok(mut state) = self.state.lock() {
+            state.cap = cap;
+            trim(&mut state);
+        }
+    }
+
+    pub fn clear(&self) {
+        self.set_cap(0);
+    }
+}
+
+fn trim(state: &mut PoolState) {
+    let mut keys: Vec<PoolKey> = state.free.keys().copied().collect();
+    keys.sort_by_key(|key| std::cmp::Reverse(key.size_class));
+
+    for key in keys {
+        while state.stats.bytes_held > state.cap {
+            let released = match state.free.get_mut(&key) {
+                Some(free) => free.pop(),
+                None => None,
+            };
+            match released {
+                Some(_) => {
+                    state.stats.bytes_held -= key.size_class;
+                    state.stats.releases += 1;
+                }
+                None => break,
+            }
+        }
+    }
+    state.free.retain(|_, free| !free.is_empty());
+}
+
+impl<T> PooledBuffer<T> {
+    pub fn capacity(&self) -> usize {
+        self.key.size_class / std::cmp::max(std::mem::size_of::<T>(), 1)
+    }
+}
+
+impl<T> Deref for PooledBuffer<T> {
+    type Target = memory::Buffer<T>;
+
+    fn deref(&self) -> &memory::Buffer<T> {
+        self.buffer.as_ref().expect("pooled buffer already returned")
+    }
+}
+
+impl<T> DerefMut for PooledBuffer<T> {
+    fn deref_mut(&mut self) -> &mut memory::Buffer<T> {
+        self.buffer.as_mut().expect("pooled buffer already returned")
+    }
+}
+
+impl<T> Drop for PooledBuffer<T> {
+    fn drop(&mut self) {
+        let buffer = match self.buffer.take() {
+            Some(buffer) => retype::<T, u8>(buffer),
+            None => return,
+        };
+
+        let mut state = match self.pool.lock() {
+            Ok(state) => state,
+            Err(_) => return,
+        };
+
+        state.stats.bytes_in_use -= self.key.size_class;
+        if state.stats.bytes_held + self.key.size_class > state.cap {
+            state.stats.releases += 1;
+            return;
+        }
+
+        state.stats.bytes_held += self.key.size_class;
+        state.free.entry(self.key).or_default().push(buffer);
+    }
+}
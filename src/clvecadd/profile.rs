@@ -0,0 +1,127 @@
+use opencl3::event;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Timing {
+    pub queued: u64,
+    pub submit: u64,
+    pub start: u64,
+    pub end: u64,
+}
+
+impl Timing {
+    pub fn from_event(event: &event::Event) -> Result<Timing, String> {
+        let queued = match event.profiling_command_queued() {
+            Ok(queued) => queued,
+            Err(error) => return Err(format!("not able to read queued time: {}", error)),
+        };
+
+        let submit = match event.profiling_command_submit() {
+            Ok(submit) => submit,
+            Err(error) => return Err(format!("not able to read submit time: {}", error)),
+        };
+
+        let start = match event.profiling_command_start() {
+            Ok(start) => start,
+            Err(error) => return Err(format!("not able to read start time: {}", error)),
+        };
+
+        let end = match event.profiling_command_end() {
+            Ok(end) => end,
+            Err(error) => return Err(format!("not able to read end time: {}", error)),
+        };
+
+        Ok(Timing {
+            queued,
+            submit,
+            start,
+            end,
+        })
+    }
+
+    pub fn queue_delay(&self) -> u64 {
+        self.submit.saturating_sub(self.queued)
+    }
+
+    pub fn submit_delay(&self) -> u64 {
+        self.start.saturating_sub(self.submit)
+    }
+
+    pub fn duration(&self) -> u64 {
+        self.end.saturating_sub(self.start)
+    }
+
+    pub fn latency(&self) -> u64 {
+        self.end.saturating_sub(self.queued)
+    }
+}
+
+pub fn get_timings(events: &[event::Event]) -> Result<Vec<Timing>, String> {
+    let mut timings = Vec::new();
+    for event in events {
+        match Timing::from_event(event) {
+            Ok(timing) => timings.push(timing),
+            Err(error) => return Err(error),
+        };
+    }
+
+    Ok(timings)
+}
+
+pub fn get_span(timings: &[Timing]) -> u64 {
+    let start = timings.iter().map(|timing| timing.start).min();
+    let end = timings.iter().map(|timing| timing.end).max();
+    match (start, end) {
+        (Some(start), Some(end)) => end.saturating_sub(start),
+        _ => 0,
+    }
+}
+
+pub fn get_bandwidth_gbs(bytes: usize, nanos: u64) -> f64 {
+    if nanos == 0 {
+        return 0.0;
+    }
+    bytes as f64 / nanos as f64
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VecAddReport {
+    pub uploads: Vec<Timing>,
+    pub kernel: Timing,
+    pub download: Timing,
+    pub bytes_uploaded: usize,
+    pub bytes_downloaded: usize,
+    pub bytes_processed: usize,
+}
+
+impl VecAddReport {
+    pub fn upload_time(&self) -> u64 {
+        get_span(&self.uploads)
+    }
+
+    pub fn kernel_time(&self) -> u64 {
+        self.kernel.duration()
+    }
+
+    pub fn download_time(&self) -> u64 {
+        self.download.duration()
+    }
+
+    pub fn total_time(&self) -> u64 {
+        let mut timings = self.uploads.clone();
+        timings.push(self.kernel);
+        timings.push(self.download);
+        get_span(&timings)
+    }
+
+    pub fn upload_bandwidth_gbs(&self) -> f64 {
+        get_bandwidth_gbs(self.bytes_uploaded, self.upload_time())
+    }
+
+    pub fn download_bandwidth_gbs(&self) -> f64 {
+        get_bandwidth_gbs(self.bytes_downloaded, self.download_time())
+    }
+
+    pub fn kernel_bandwidth_gbs(&self) -> f64 {
+        get_bandwidth_gbs(self.bytes_processed, self.kernel_time())
+    }
+}
@@ -0,0 +1,512 @@
+use opencl3::context;
+use opencl3::error_codes::ClError;
+use opencl3::types::cl_context;
+use opencl3::kernel;
+use opencl3::program;
+use log::debug;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+
+use crate::clvecadd::aot;
+use crate::clvecadd::buildlog;
+use crate::clvecadd::caps;
+use crate::clvecadd::container;
+use crate::clvecadd::elementwise;
+use crate::clvecadd::exec;
+use crate::clvecadd::il;
+use crate::clvecadd::options;
+use crate::clvecadd::traits;
+
+pub const NUMERIC_TYPES: [&str; 11] = [
+    "char", "short", "int", "long", "uchar", "ushort", "uint", "ulong", "half", "float", "double",
+];
+
+pub const INTEGER_TYPES: [&str; 8] = ["char", "short", "int", "long", "uchar", "ushort", "uint", "ulong"];
+
+pub const FLOAT_TYPES: [&str; 3] = ["half", "float", "double"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Kernels {
+    AddVectors,
+    AddVectorsInplace,
+    SubVectors,
+    SubVectorsInplace,
+    MulVectors,
+    DivVectors,
+    RemVectors,
+    MinVectors,
+    MaxVectors,
+    AbsVector,
+    NegVector,
+    ScaleVector,
+    AddScalar,
+    Axpy,
+    Axpby,
+    AddVectorsSat,
+    SubVectorsSat,
+    AddVectorsChecked,
+    SubVectorsChecked,
+    SqrtVector,
+    ExpVector,
+    LogVector,
+    SinVector,
+    PowVectors,
+    EqVectors,
+    LtVectors,
+    GtVectors,
+    LeVectors,
+    GeVectors,
+    SelectVectors,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArgKind {
+    Input,
+    Output,
+    InOut,
+    Scalar,
+    MaskInput,
+    MaskOutput,
+    // a single word the kernel sets, not sized by the element count
+    Flag,
+    Count,
+}
+
+pub struct KernelInfo {
+    pub source: &'static aot::KernelSource,
+    pub entry_point: &'static str,
+    pub args: &'static [ArgKind],
+    pub element_types: &'static [&'static str],
+}
+
+const BINARY_ARGS: [ArgKind; 4] = [ArgKind::Input, ArgKind::Input, ArgKind::Output, ArgKind::Count];
+const INPLACE_ARGS: [ArgKind; 3] = [ArgKind::InOut, ArgKind::Input, ArgKind::Count];
+const UNARY_ARGS: [ArgKind; 3] = [ArgKind::Input, ArgKind::Output, ArgKind::Count];
+const SCALE_ARGS: [ArgKind; 4] = [ArgKind::Scalar, ArgKind::Input, ArgKind::Output, ArgKind::Count];
+const ADD_SCALAR_ARGS: [ArgKind; 4] = [ArgKind::Input, ArgKind::Scalar, ArgKind::Output, ArgKind::Count];
+const AXPY_ARGS: [ArgKind; 4] = [ArgKind::Scalar, ArgKind::Input, ArgKind::InOut, ArgKind::Count];
+const CHECKED_ARGS: [ArgKind; 5] = [ArgKind::Input, ArgKind::Input, ArgKind::Output, ArgKind::Flag, ArgKind::Count];
+const COMPARE_ARGS: [ArgKind; 4] = [ArgKind::Input, ArgKind::Input, ArgKind::MaskOutput, ArgKind::Count];
+const SELECT_ARGS: [ArgKind; 5] = [ArgKind::MaskInput, ArgKind::Input, ArgKind::Input, ArgKind::Output, ArgKind::Count];
+const AXPBY_ARGS: [ArgKind; 5] = [ArgKind::Scalar, ArgKind::Input, ArgKind::Scalar, ArgKind::InOut, ArgKind::Count];
+
+impl Kernels {
+    pub const ALL: [Kernels; 30] = [
+        Kernels::AddVectors,
+        Kernels::AddVectorsInplace,
+        Kernels::SubVectors,
+        Kernels::SubVectorsInplace,
+        Kernels::MulVectors,
+        Kernels::DivVectors,
+        Kernels::RemVectors,
+        Kernels::MinVectors,
+        Kernels::MaxVectors,
+        Kernels::AbsVector,
+        Kernels::NegVector,
+        Kernels::ScaleVector,
+        Kernels::AddScalar,
+        Kernels::Axpy,
+        Kernels::Axpby,
+        Kernels::AddVectorsSat,
+        Kernels::SubVectorsSat,
+        Kernels::AddVectorsChecked,
+        Kernels::SubVectorsChecked,
+        Kernels::SqrtVector,
+        Kernels::ExpVector,
+        Kernels::LogVector,
+        Kernels::SinVector,
+        Kernels::PowVectors,
+        Kernels::EqVectors,
+        Kernels::LtVectors,
+        Kernels::GtVectors,
+        Kernels::LeVectors,
+        Kernels::GeVectors,
+        Kernels::SelectVectors,
+    ];
+
+    pub fn info(&self) -> KernelInfo {
+        let (entry_point, args): (&'static str, &'static [ArgKind]) = match self {
+            Kernels::AddVectors => ("addVectors", &BINARY_ARGS),
+            Kernels::AddVectorsInplace => ("addVectorsInplace", &INPLACE_ARGS),
+            Kernels::SubVectors => ("subVectors", &BINARY_ARGS),
+            Kernels::SubVectorsInplace => ("subVectorsInplace", &INPLACE_ARGS),
+            Kernels::MulVectors => ("mulVectors", &BINARY_ARGS),
+            Kernels::DivVectors => ("divVectors", &BINARY_ARGS),
+            Kernels::RemVectors => ("remVectors", &BINARY_ARGS),
+            Kernels::MinVectors => ("minVectors", &BINARY_ARGS),
+            Kernels::MaxVectors => ("maxVectors", &BINARY_ARGS),
+            Kernels::AbsVector => ("absVector", &UNARY_ARGS),
+            Kernels::NegVector => ("negVector", &UNARY_ARGS),
+            Kernels::ScaleVector => ("scaleVector", &SCALE_ARGS),
+            Kernels::AddScalar => ("addScalar", &ADD_SCALAR_ARGS),
+            Kernels::Axpy => ("axpy", &AXPY_ARGS),
+            Kernels::Axpby => ("axpby", &AXPBY_ARGS),
+            Kernels::AddVectorsSat => ("addVectorsSat", &BINARY_ARGS),
+            Kernels::SubVectorsSat => ("subVectorsSat", &BINARY_ARGS),
+            Kernels::AddVectorsChecked => ("addVectorsChecked", &CHECKED_ARGS),
+            Kernels::SubVectorsChecked => ("subVectorsChecked", &CHECKED_ARGS),
+            Kernels::SqrtVector => ("sqrtVector", &UNARY_ARGS),
+            Kernels::ExpVector => ("expVector", &UNARY_ARGS),
+            Kernels::LogVector => ("logVector", &UNARY_ARGS),
+            Kernels::SinVector => ("sinVector", &UNARY_ARGS),
+            Kernels::PowVectors => ("powVectors", &BINARY_ARGS),
+            Kernels::EqVectors => ("eqVectors", &COMPARE_ARGS),
+            Kernels::LtVectors => ("ltVectors", &COMPARE_ARGS),
+            Kernels::GtVectors => ("gtVectors", &COMPARE_ARGS),
+            Kernels::LeVectors => ("leVectors", &COMPARE_ARGS),
+            Kernels::GeVectors => ("geVectors", &COMPARE_ARGS),
+            Kernels::SelectVectors => ("selectVectors", &SELECT_ARGS),
+        };
+
+        // saturation and overflow checks only exist for integers, math functions for floats
+        let element_types: &'static [&'static str] = match self {
+            Kernels::AddVectorsSat
+            | Kernels::SubVectorsSat
+            | Kernels::AddVectorsChecked
+            | Kernels::SubVectorsChecked => &INTEGER_TYPES,
+            Kernels::SqrtVector
+            | Kernels::ExpVector
+            | Kernels::LogVector
+            | Kernels::SinVector
+            | Kernels::PowVectors => &FLOAT_TYPES,
+            _ => &NUMERIC_TYPES,
+        };
+
+        KernelInfo {
+            source: &aot::KERNEL_SOURCES[0],
+            entry_point,
+            args,
+            element_types,
+        }
+    }
+
+    pub fn entry_point(&self) -> &'static str {
+        self.info().entry_point
+    }
+
+    pub fn supports(&self, element_type: &str) -> bool {
+        self.info().element_types.contains(&element_type)
+    }
+}
+
+pub fn find_kernel(file: &str, entry_point: &str) -> Option<Kernels> {
+    Kernels::ALL.into_iter().find(|handle| {
+        let info = handle.info();
+        info.entry_point == entry_point && Path::new(info.source.path).file_name() == Some(file.as_ref())
+    })
+}
+
+fn create_kernel(prog: &program::Program, entry_point: &str) -> Result<kernel::Kernel, String> {
+    match kernel::Kernel::create(prog, entry_point) {
+        Ok(kernel) => Ok(kernel),
+        Err(error) => Err(format!("not able to get kernel {}: {}", entry_point, error)),
+    }
+}
+
+// tries a cached vendor binary, then a SPIR-V module from scripts/build_spirv.sh,
+// then the source, and refreshes the cached binary afterwards
+pub fn build_program(
+    context: &context::Context,
+    source: &aot::KernelSource,
+    element_type: &str,
+) -> Result<program::Program, String> {
+    match caps::check_type_name_support(context, element_type) {
+        Ok(_) => (),
+        Err(error) => return Err(error.into()),
+    };
+
+    // the same options as clvecadd-build, so shipped binaries are found
+    let options = match aot::get_build_options(context, source, element_type) {
+        Ok(options) => options,
+        Err(error) => return Err(error),
+    };
+
+    let device_id = match context.devices().first() {
+        Some(device_id) => *device_id,
+        None => return Err(String::from("context has no devices")),
+    };
+
+    let cache_dir = container::get_cache_dir();
+
+    // one binary per device and option set, otherwise a binary built for another type gets loaded
+    let path_to_bin = match container::get_binary_path(&cache_dir, source.name, device_id, &options) {
+        Ok(path_to_bin) => path_to_bin,
+        Err(error) => return Err(error),
+    };
+
+    // SPIR-V modules are specialised per element type since defines cannot be applied to IL
+    let path_to_il = cache_dir.join("spirv").join(format!("{}_{}.spv", source.name, element_type));
+
+    let sources = [Path::new(source.path)];
+    let binary = [path_to_bin.as_path()];
+    let il = [path_to_il.as_path()];
+
+    let mut progs = match exec::create_and_build_from_binaries(context, &binary, &options, &sources) {
+        Ok(progs) => progs,
+        Err(error) => {
+            debug!("{}", error);
+            let from_il = match il::check_module_source(&path_to_il, sources[0]) {
+                Ok(_) => il::create_and_build_from_il(context, &il, ""),
+                Err(error) => Err(error),
+            };
+            match from_il {
+                Ok(progs) => progs,
+                Err(error) => {
+                    debug!("{}", error);
+                    match exec::create_and_build_from_sources(context, &sources, &options) {
+                        Ok(progs) => progs,
+                        Err(error) => return Err(error),
+                    }
+                }
+            }
+        }
+    };
+
+    if progs.is_empty() {
+        return Err(String::from("no programs built"));
+    }
+    let prog = progs.remove(0);
+
+    let written = match fs::read(source.path) {
+        Ok(content) => container::write_binary(&prog, &path_to_bin, &options, &content),
+        Err(error) => Err(format!("error reading source file {}: {}", source.path, error)),
+    };
+    match written {
+        Ok(_) => (),
+        Err(error) => debug!("{}", error),
+    };
+
+    Ok(prog)
+}
+
+// generated sources have no file or SPIR-V module, so only the cached binary is tried
+pub fn build_generated_program(
+    context: &context::Context,
+    stem: &str,
+    content: &str,
+    element_type: &str,
+) -> Result<program::Program, String> {
+    match caps::check_type_name_support(context, element_type) {
+        Ok(_) => (),
+        Err(error) => return Err(error.into()),
+    };
+
+    let options = match options::BuildOptions::new().detect_standard(context) {
+        Ok(build_options) => build_options.define("ARRAY_TYPE", element_type).to_string(),
+        Err(error) => return Err(error),
+    };
+
+    let device_id = match context.devices().first() {
+        Some(device_id) => *device_id,
+        None => return Err(String::from("context has no devices")),
+    };
+
+    let path_to_bin = match container::get_binary_path(&container::get_cache_dir(), stem, device_id, &options) {
+        Ok(path_to_bin) => path_to_bin,
+        Err(error) => return Err(error),
+    };
+
+    let cached = match fs::read(&path_to_bin) {
+        Ok(binary) => exec::create_and_build_from_container(
+            context,
+            &path_to_bin.display().to_string(),
+            &binary,
+            &options,
+            content.as_bytes(),
+        ),
+        Err(error) => Err(format!("error reading binary file {}: {}", path_to_bin.display(), error)),
+    };
+
+    let prog = match cached {
+        Ok(prog) => prog,
+        Err(error) => {
+            debug!("{}", error);
+            match buildlog::build_from_source(context, stem, content, &options) {
+                Ok((prog, _)) => prog,
+                Err(failure) => return Err(failure.into()),
+            }
+        }
+    };
+
+    match container::write_binary(&prog, &path_to_bin, &options, content.as_bytes()) {
+        Ok(_) => (),
+        Err(error) => debug!("{}", error),
+    };
+
+    Ok(prog)
+}
+
+type ProgramKey = (String, String);
+
+// the cached programs of one context, which is retained for as long as the
+// entry exists so its address cannot be handed to a new context meanwhile
+struct ContextPrograms {
+    context: cl_context,
+    programs: HashMap<ProgramKey, program::Program>,
+}
+
+impl ContextPrograms {
+    fn new(context: &context::Context) -> Result<ContextPrograms, String> {
+        match unsafe { context::context::retain_context(context.get()) } {
+            Ok(_) => Ok(ContextPrograms {
+                context: context.get(),
+                programs: HashMap::new(),
+            }),
+            Err(error) => Err(format!("not able to retain context: {}", ClError(error))),
+        }
+    }
+}
+
+impl Drop for ContextPrograms {
+    fn drop(&mut self) {
+        // programs hold their own reference, release them before the context
+        self.programs.clear();
+        unsafe {
+            let _ = context::context::release_context(self.context);
+        }
+    }
+}
+
+unsafe impl Send for ContextPrograms {}
+
+#[derive(Default)]
+pub struct KernelRegistry {
+    contexts: Mutex<HashMap<usize, ContextPrograms>>,
+}
+
+impl KernelRegistry {
+    pub fn new() -> KernelRegistry {
+        KernelRegistry {
+            contexts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn get<T: traits::OpenclNum + traits::HasOpenclString>(
+        &self,
+        context: &context::Context,
+        handle: Kernels,
+    ) -> Result<kernel::Kernel, String> {
+        self.get_for_type(context, handle, <T>::as_opencl_string())
+    }
+
+    pub fn get_for_type(
+        &self,
+        context: &context::Context,
+        handle: Kernels,
+        element_type: &str,
+    ) -> Result<kernel::Kernel, String> {
+        let info = handle.info();
+        if !handle.supports(element_type) {
+            return Err(format!("{} does not support element type {}", info.entry_point, element_type));
+        }
+
+        let key = (info.source.name.to_string(), element_type.to_string());
+        self.get_or_build(context, key, info.entry_point, || build_program(context, info.source, element_type))
+    }
+
+    pub fn get_generated<T: traits::OpenclNum + traits::HasOpenclString>(
+        &self,
+        context: &context::Context,
+        op: &elementwise::ElementwiseOp,
+    ) -> Result<kernel::Kernel, String> {
+        let element_type = <T>::as_opencl_string();
+        let content = match op.generate_source() {
+            Ok(content) => content,
+            Err(error) => return Err(error),
+        };
+        let stem = match op.get_stem() {
+            Ok(stem) => stem,
+            Err(error) => return Err(error),
+        };
+
+        let key = (stem.clone(), element_type.to_string());
+        self.get_or_build(context, key, op.name(), || {
+            build_generated_program(context, &stem, &content, element_type)
+        })
+    }
+
+    fn get_or_build(
+        &self,
+        context: &context::Context,
+        key: ProgramKey,
+        entry_point: &str,
+        build: impl FnOnce() -> Result<program::Program, String>,
+    ) -> Result<kernel::Kernel, String> {
+        let address = context.get() as usize;
+        match self.contexts.lock() {
+            Ok(contexts) => {
+                if let Some(prog) = contexts.get(&address).and_then(|entry| entry.programs.get(&key)) {
+                    return create_kernel(prog, entry_point);
+                }
+            }
+            Err(error) => return Err(format!("kernel registry lock poisoned: {}", error)),
+        };
+
+        // builds can take seconds, so they run without the lock and another
+        // thread may have inserted the same program in the meantime
+        let built = match build() {
+            Ok(prog) => prog,
+            Err(error) => return Err(error),
+        };
+
+        let mut contexts = match self.contexts.lock() {
+            Ok(contexts) => contexts,
+            Err(error) => return Err(format!("kernel registry lock poisoned: {}", error)),
+        };
+
+        let programs = match contexts.entry(address) {
+            Entry::Occupied(entry) => &mut entry.into_mut().programs,
+            Entry::Vacant(entry) => match ContextPrograms::new(context) {
+                Ok(created) => &mut entry.insert(created).programs,
+                Err(error) => return Err(error),
+            },
+        };
+        let prog = programs.entry(key).or_insert(built);
+
+        create_kernel(prog, entry_point)
+    }
+
+    // drops the programs built for `context` and the registry's reference on it,
+    // returns how many programs were evicted
+    pub fn remove_context(&self, context: &context::Context) -> usize {
+        match self.contexts.lock() {
+            Ok(mut contexts) => match contexts.remove(&(context.get() as usize)) {
+                Some(entry) => entry.programs.len(),
+                None => 0,
+            },
+            Err(_) => 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self.contexts.lock() {
+            Ok(contexts) => contexts.values().map(|entry| entry.programs.len()).sum(),
+            Err(_) => 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut contexts) = self.contexts.lock() {
+            contexts.clear();
+        }
+    }
+}
+
+pub fn global_registry() -> &'static KernelRegistry {
+    static REGISTRY: OnceLock<KernelRegistry> = OnceLock::new();
+    REGISTRY.get_or_init(KernelRegistry::new)
+}
+
+pub fn get_kernel<T: traits::OpenclNum + traits::HasOpenclString>(
+    context: &context::Context,
+    handle: Kernels,
+) -> Result<kernel::Kernel, String> {
+    global_registry().get::<T>(context, handle)
+}
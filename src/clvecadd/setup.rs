@@ -6,6 +6,7 @@ use log::info;
 
 use crate::clvecadd::exec;
 
+#[derive(Clone, Copy)]
 pub enum DeviceType {
     All,
     Gpu,
@@ -70,6 +71,14 @@ pub fn append_devices_from_platform(
 }
 
 pub fn get_all_gpus() -> Result<Vec<device::Device>, String> {
+    get_all_devices(DeviceType::Gpu)
+}
+
+pub fn get_all_cpus() -> Result<Vec<device::Device>, String> {
+    get_all_devices(DeviceType::Cpu)
+}
+
+pub fn get_all_devices(dtype: DeviceType) -> Result<Vec<device::Device>, String> {
     let platforms = match platform::get_platforms() {
         Ok(platforms) => platforms,
         Err(error) => return Err(format!("error getting platforms: {}", error)),
@@ -78,7 +87,7 @@ pub fn get_all_gpus() -> Result<Vec<device::Device>, String> {
     let mut devices: Vec<device::Device> = Vec::new();
     for platform in platforms {
         log_platform_info(&platform);
-        match append_devices_from_platform(&mut devices, &platform, DeviceType::Gpu) {
+        match append_devices_from_platform(&mut devices, &platform, dtype) {
             Ok(_) => (),
             Err(error) => return Err(error),
         };
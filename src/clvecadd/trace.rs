@@ -0,0 +1,352 @@
+use log::debug;
+use opencl3::command_queue;
+use opencl3::event;
+use opencl3::kernel;
+use opencl3::types::cl_command_queue;
+use serde_json::{json, Value};
+use std::fs;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+
+use crate::clvecadd::events;
+use crate::clvecadd::profile;
+
+pub type RecordId = usize;
+
+struct Record {
+    event: event::Event,
+    track: usize,
+    name: String,
+    kernel: Option<String>,
+    bytes: usize,
+    dependencies: Vec<RecordId>,
+}
+
+// holds a reference on the queue, so its handle cannot be reused by another
+// queue while the track exists
+struct Track {
+    queue: cl_command_queue,
+    name: String,
+}
+
+impl Drop for Track {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = command_queue::release_command_queue(self.queue);
+        }
+    }
+}
+
+unsafe impl Send for Track {}
+
+#[derive(Default)]
+pub struct TraceRecorder {
+    records: Vec<Record>,
+    tracks: Vec<Track>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub id: RecordId,
+    pub track: usize,
+    pub name: String,
+    pub kernel: Option<String>,
+    pub bytes: usize,
+    pub dependencies: Vec<RecordId>,
+    pub timing: profile::Timing,
+}
+
+impl TraceRecorder {
+    pub fn new() -> TraceRecorder {
+        TraceRecorder {
+            records: Vec::new(),
+            tracks: Vec::new(),
+        }
+    }
+
+    pub fn name_queue(&mut self, queue: &command_queue::CommandQueue, name: &str) -> Result<(), String> {
+        match self.get_track(queue) {
+            Ok(track) => {
+                self.tracks[track].name = name.to_string();
+                Ok(())
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    pub fn record(
+        &mut self,
+        queue: &command_queue::CommandQueue,
+        event: &event::Event,
+        name: &str,
+        kernel: Option<&str>,
+        bytes: usize,
+        dependencies: &[RecordId],
+    ) -> Result<RecordId, String> {
+        let id = self.records.len();
+        if let Some(dependency) = dependencies.iter().find(|dependency| **dependency >= id) {
+            return Err(format!("record {} cannot depend on unknown record {}", id, dependency));
+        }
+
+        let track = match self.get_track(queue) {
+            Ok(track) => track,
+            Err(error) => return Err(error),
+        };
+
+        // the recorder keeps its own reference so the caller may drop the event
+        match unsafe { event::retain_event(event.get()) } {
+            Ok(_) => (),
+            Err(error) => return Err(format!("not able to retain event: {}", error)),
+        };
+
+        self.records.push(Record {
+            event: event::Event::new(event.get()),
+            track,
+            name: name.to_string(),
+            kernel: kernel.map(|kernel| kernel.to_string()),
+            bytes,
+            dependencies: dependencies.to_vec(),
+        });
+        Ok(id)
+    }
+
+    // the records whose events are in `wait_list`
+    pub fn find_records(&self, wait_list: &events::EventList) -> Vec<RecordId> {
+        let waited = wait_list.as_slice();
+        (0..self.records.len())
+            .filter(|id| waited.contains(&self.records[*id].event.get()))
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.records.clear();
+    }
+
+    pub fn collect(&self) -> Result<Vec<TraceEntry>, String> {
+        let mut entries = Vec::new();
+        for (id, record) in self.records.iter().enumerate() {
+            match record.event.wait() {
+                Ok(_) => (),
+                Err(error) => return Err(format!("error waiting for {}: {}", record.name, error)),
+            };
+
+            let timing = match profile::Timing::from_event(&record.event) {
+                Ok(timing) => timing,
+                Err(error) => return Err(format!("{} ({})", error, record.name)),
+            };
+
+            entries.push(TraceEntry {
+                id,
+                track: record.track,
+                name: record.name.clone(),
+                kernel: record.kernel.clone(),
+                bytes: record.bytes,
+                dependencies: record.dependencies.clone(),
+                timing,
+            });
+        }
+
+        Ok(entries)
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        let entries = match self.collect() {
+            Ok(entries) => entries,
+            Err(error) => return Err(error),
+        };
+
+        let track_names: Vec<String> = self.tracks.iter().map(|track| track.name.clone()).collect();
+        get_trace_json(&entries, &track_names)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        let content = match self.to_json() {
+            Ok(content) => content,
+            Err(error) => return Err(error),
+        };
+
+        match fs::write(path, content) {
+            Ok(_) => Ok(()),
+            Err(error) => Err(format!("not able to write {}: {}", path.display(), error)),
+        }
+    }
+
+    fn get_track(&mut self, queue: &command_queue::CommandQueue) -> Result<usize, String> {
+        if let Some(track) = self.tracks.iter().position(|track| track.queue == queue.get()) {
+            return Ok(track);
+        }
+
+        match unsafe { command_queue::retain_command_queue(queue.get()) } {
+            Ok(_) => (),
+            Err(error) => return Err(format!("not able to retain command queue: {}", error)),
+        };
+
+        self.tracks.push(Track {
+            queue: queue.get(),
+            name: format!("queue {}", self.tracks.len()),
+        });
+        Ok(self.tracks.len() - 1)
+    }
+}
+
+// while a trace is active the shared enqueue paths in buffer, image and exec
+// record every command they submit
+fn active_recorder() -> &'static Mutex<Option<TraceRecorder>> {
+    static RECORDER: OnceLock<Mutex<Option<TraceRecorder>>> = OnceLock::new();
+    RECORDER.get_or_init(|| Mutex::new(None))
+}
+
+// replaces a trace that is still active
+pub fn start() {
+    if let Ok(mut active) = active_recorder().lock() {
+        *active = Some(TraceRecorder::new());
+    }
+}
+
+pub fn stop() -> Option<TraceRecorder> {
+    match active_recorder().lock() {
+        Ok(mut active) => active.take(),
+        Err(_) => None,
+    }
+}
+
+pub fn is_active() -> bool {
+    match active_recorder().lock() {
+        Ok(active) => active.is_some(),
+        Err(_) => false,
+    }
+}
+
+// a failed record only loses the trace entry, the command itself was submitted
+fn record_active(
+    queue: &command_queue::CommandQueue,
+    event: &event::Event,
+    name: &str,
+    kernel: Option<&str>,
+    bytes: usize,
+    wait_list: &events::EventList,
+) {
+    if let Ok(mut active) = active_recorder().lock() {
+        if let Some(recorder) = active.as_mut() {
+            let dependencies = recorder.find_records(wait_list);
+            match recorder.record(queue, event, name, kernel, bytes, &dependencies) {
+                Ok(_) => (),
+                Err(error) => debug!("not able to trace {}: {}", name, error),
+            };
+        }
+    }
+}
+
+pub fn record_transfer(
+    queue: &command_queue::CommandQueue,
+    event: &event::Event,
+    name: &str,
+    bytes: usize,
+    wait_list: &events::EventList,
+) {
+    if is_active() {
+        record_active(queue, event, name, None, bytes, wait_list);
+    }
+}
+
+pub fn record_kernel(
+    queue: &command_queue::CommandQueue,
+    event: &event::Event,
+    kernel: &kernel::Kernel,
+    wait_list: &events::EventList,
+) {
+    if is_active() {
+        let name = match kernel.function_name() {
+            Ok(name) => name,
+            Err(_) => String::from("kernel"),
+        };
+        record_active(queue, event, &name, Some(&name), 0, wait_list);
+    }
+}
+
+fn get_micros(nanos: u64) -> f64 {
+    nanos as f64 / 1000.0
+}
+
+pub fn get_trace_json(entries: &[TraceEntry], track_names: &[String]) -> Result<String, String> {
+    let origin = entries.iter().map(|entry| entry.timing.start).min().unwrap_or(0);
+
+    let mut trace_events: Vec<Value> = Vec::new();
+    for (track, name) in track_names.iter().enumerate() {
+        trace_events.push(json!({
+            "name": "thread_name",
+            "ph": "M",
+            "pid": 0,
+            "tid": track,
+            "args": { "name": name },
+        }));
+    }
+
+    for entry in entries {
+        let ts = get_micros(entry.timing.start.saturating_sub(origin));
+        trace_events.push(json!({
+            "name": entry.name,
+            "cat": if entry.kernel.is_some() { "kernel" } else { "transfer" },
+            "ph": "X",
+            "pid": 0,
+            "tid": entry.track,
+            "ts": ts,
+            "dur": get_micros(entry.timing.duration()),
+            "args": {
+                "id": entry.id,
+                "kernel": entry.kernel,
+                "bytes": entry.bytes,
+                "dependencies": entry.dependencies,
+                "queue_delay_ns": entry.timing.queue_delay(),
+                "submit_delay_ns": entry.timing.submit_delay(),
+            },
+        }));
+
+        // flow arrows from the end of each dependency to the start of this command
+        for dependency in &entry.dependencies {
+            let source = match entries.iter().find(|other| other.id == *dependency) {
+                Some(source) => source,
+                None => return Err(format!("record {} depends on missing record {}", entry.id, dependency)),
+            };
+            let flow = format!("{}->{}", source.id, entry.id);
+            let end = get_micros(source.timing.end.saturating_sub(origin));
+            trace_events.push(json!({
+                "name": "dependency",
+                "cat": "dependency",
+                "ph": "s",
+                "id": flow,
+                "pid": 0,
+                "tid": source.track,
+                "ts": end,
+            }));
+            trace_events.push(json!({
+                "name": "dependency",
+                "cat": "dependency",
+                "ph": "f",
+                "bp": "e",
+                "id": flow,
+                "pid": 0,
+                "tid": entry.track,
+                "ts": ts,
+            }));
+        }
+    }
+
+    let trace = json!({
+        "traceEvents": trace_events,
+        "displayTimeUnit": "ns",
+    });
+
+    match serde_json::to_string_pretty(&trace) {
+        Ok(content) => Ok(content),
+        Err(error) => Err(format!("not able to serialize trace: {}", error)),
+    }
+}
@@ -1,10 +1,34 @@
 use half::f16;
+use opencl3::memory;
+use opencl3::types::{cl_channel_order, cl_channel_type, cl_image_format};
 
 pub trait HasOpenclString {
     fn as_opencl_string() -> &'static str;
 }
 
-pub trait OpenclNum {}
+pub trait HasImageFormat {
+    fn channel_order() -> cl_channel_order;
+    fn channel_data_type() -> cl_channel_type;
+    fn image_access_suffix() -> &'static str;
+
+    fn image_format() -> cl_image_format {
+        cl_image_format {
+            image_channel_order: Self::channel_order(),
+            image_channel_data_type: Self::channel_data_type(),
+        }
+    }
+}
+
+pub trait OpenclNum {
+    fn required_extension() -> Option<&'static str> {
+        None
+    }
+
+    // the value when it can be passed as a kernel's ulong element count
+    fn as_count(&self) -> Option<u64> {
+        None
+    }
+}
 
 impl OpenclNum for i8 {}
 impl OpenclNum for i16 {}
@@ -13,10 +37,54 @@ impl OpenclNum for i64 {}
 impl OpenclNum for u8 {}
 impl OpenclNum for u16 {}
 impl OpenclNum for u32 {}
-impl OpenclNum for u64 {}
-impl OpenclNum for f16 {}
+impl OpenclNum for u64 {
+    fn as_count(&self) -> Option<u64> {
+        Some(*self)
+    }
+}
+impl OpenclNum for f16 {
+    fn required_extension() -> Option<&'static str> {
+        Some("cl_khr_fp16")
+    }
+}
 impl OpenclNum for f32 {}
-impl OpenclNum for f64 {}
+impl OpenclNum for f64 {
+    fn required_extension() -> Option<&'static str> {
+        Some("cl_khr_fp64")
+    }
+}
+
+pub fn get_opencl_num_types() -> Vec<&'static str> {
+    vec![
+        <i8>::as_opencl_string(),
+        <i16>::as_opencl_string(),
+        <i32>::as_opencl_string(),
+        <i64>::as_opencl_string(),
+        <u8>::as_opencl_string(),
+        <u16>::as_opencl_string(),
+        <u32>::as_opencl_string(),
+        <u64>::as_opencl_string(),
+        <f16>::as_opencl_string(),
+        <f32>::as_opencl_string(),
+        <f64>::as_opencl_string(),
+    ]
+}
+
+pub fn get_required_extension(opencl_type: &str) -> Option<&'static str> {
+    if opencl_type == <f16>::as_opencl_string() {
+        <f16>::required_extension()
+    } else if opencl_type == <f64>::as_opencl_string() {
+        <f64>::required_extension()
+    } else {
+        None
+    }
+}
+
+pub fn is_float_type(opencl_type: &str) -> bool {
+    opencl_type == <f16>::as_opencl_string()
+        || opencl_type == <f32>::as_opencl_string()
+        || opencl_type == <f64>::as_opencl_string()
+}
 
 impl HasOpenclString for i8 {
     fn as_opencl_string() -> &'static str {
@@ -84,6 +152,8 @@ impl HasOpenclString for f64 {
     }
 }
 
+// OpenCL C forbids bool in kernel arguments and buffers, so bool is not an
+// OpenclNum and device masks are u8, see mask::to_bools
 impl HasOpenclString for bool {
     fn as_opencl_string() -> &'static str {
         "bool"
@@ -95,3 +165,129 @@ impl HasOpenclString for char {
         "char"
     }
 }
+
+impl HasImageFormat for i8 {
+    fn channel_order() -> cl_channel_order {
+        memory::CL_R
+    }
+
+    fn channel_data_type() -> cl_channel_type {
+        memory::CL_SIGNED_INT8
+    }
+
+    fn image_access_suffix() -> &'static str {
+        "i"
+    }
+}
+
+impl HasImageFormat for i16 {
+    fn channel_order() -> cl_channel_order {
+        memory::CL_R
+    }
+
+    fn channel_data_type() -> cl_channel_type {
+        memory::CL_SIGNED_INT16
+    }
+
+    fn image_access_suffix() -> &'static str {
+        "i"
+    }
+}
+
+impl HasImageFormat for i32 {
+    fn channel_order() -> cl_channel_order {
+        memory::CL_R
+    }
+
+    fn channel_data_type() -> cl_channel_type {
+        memory::CL_SIGNED_INT32
+    }
+
+    fn image_access_suffix() -> &'static str {
+        "i"
+    }
+}
+
+impl HasImageFormat for u8 {
+    fn channel_order() -> cl_channel_order {
+        memory::CL_R
+    }
+
+    fn channel_data_type() -> cl_channel_type {
+        memory::CL_UNSIGNED_INT8
+    }
+
+    fn image_access_suffix() -> &'static str {
+        "ui"
+    }
+}
+
+impl HasImageFormat for u16 {
+    fn channel_order() -> cl_channel_order {
+        memory::CL_R
+    }
+
+    fn channel_data_type() -> cl_channel_type {
+        memory::CL_UNSIGNED_INT16
+    }
+
+    fn image_access_suffix() -> &'static str {
+        "ui"
+    }
+}
+
+impl HasImageFormat for u32 {
+    fn channel_order() -> cl_channel_order {
+        memory::CL_R
+    }
+
+    fn channel_data_type() -> cl_channel_type {
+        memory::CL_UNSIGNED_INT32
+    }
+
+    fn image_access_suffix() -> &'static str {
+        "ui"
+    }
+}
+
+impl HasImageFormat for f16 {
+    fn channel_order() -> cl_channel_order {
+        memory::CL_R
+    }
+
+    fn channel_data_type() -> cl_channel_type {
+        memory::CL_HALF_FLOAT
+    }
+
+    fn image_access_suffix() -> &'static str {
+        "f"
+    }
+}
+
+impl HasImageFormat for f32 {
+    fn channel_order() -> cl_channel_order {
+        memory::CL_R
+    }
+
+    fn channel_data_type() -> cl_channel_type {
+        memory::CL_FLOAT
+    }
+
+    fn image_access_suffix() -> &'static str {
+        "f"
+    }
+}
+
+impl<T: HasImageFormat> HasImageFormat for [T; 4] {
+    fn channel_order() -> cl_channel_order {
+        memory::CL_RGBA
+    }
+
+    fn channel_data_type() -> cl_channel_type {
+        <T>::channel_data_type()
+    }
+
+    fn image_access_suffix() -> &'static str {
+        <T>::image_access_suffix()
+    }
+}
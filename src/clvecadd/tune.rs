@@ -0,0 +1,362 @@
+use opencl3::command_queue;
+use opencl3::device;
+use opencl3::kernel;
+use opencl3::program;
+use log::debug;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use crate::clvecadd::events;
+use crate::clvecadd::exec;
+
+pub const DEFAULT_TUNING_DB: &str = "clvecadd_tuning.db";
+pub const TUNING_DB_ENV: &str = "CLVECADD_TUNING_DB";
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TuningKey {
+    pub device: String,
+    pub kernel: String,
+    pub options: String,
+    pub size_bucket: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TuningEntry {
+    pub local: usize,
+    pub elements_per_item: usize,
+    pub nanos: u64,
+}
+
+pub struct TuningDb {
+    path: PathBuf,
+    entries: HashMap<TuningKey, TuningEntry>,
+}
+
+pub fn get_size_bucket(elements: usize) -> u32 {
+    usize::BITS - elements.leading_zeros()
+}
+
+pub fn get_tuning_key(
+    queue: &command_queue::CommandQueue,
+    kernel: &kernel::Kernel,
+    elements: usize,
+) -> Result<TuningKey, String> {
+    let device_id = match queue.device() {
+        Ok(device_id) => device_id,
+        Err(error) => return Err(format!("not able to get device of queue: {}", error)),
+    };
+    let device = device::Device::new(device_id);
+
+    let device_name = match device.name() {
+        Ok(device_name) => device_name,
+        Err(error) => return Err(format!("not able to get device name: {}", error)),
+    };
+
+    let driver_version = match device.driver_version() {
+        Ok(driver_version) => driver_version,
+        Err(error) => return Err(format!("not able to get driver version: {}", error)),
+    };
+
+    let kernel_name = match kernel.function_name() {
+        Ok(kernel_name) => kernel_name,
+        Err(error) => return Err(format!("not able to get kernel name: {}", error)),
+    };
+
+    let prog = match kernel.program() {
+        Ok(prog) => prog,
+        Err(error) => return Err(format!("not able to get kernel program: {}", error)),
+    };
+
+    let options: String = match program::get_program_build_info(prog, device_id, program::CL_PROGRAM_BUILD_OPTIONS) {
+        Ok(options) => options.into(),
+        Err(error) => return Err(format!("not able to get build options: {}", error)),
+    };
+
+    Ok(TuningKey {
+        device: format!("{} ({})", device_name.trim(), driver_version.trim()),
+        kernel: kernel_name,
+        options: options.trim().to_string(),
+        size_bucket: get_size_bucket(elements),
+    })
+}
+
+fn sanitize(field: &str) -> String {
+    field.replace(['\t', '\n'], " ")
+}
+
+impl TuningDb {
+    pub fn new(path: &Path) -> TuningDb {
+        TuningDb {
+            path: path.to_path_buf(),
+            entries: HashMap::new(),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<TuningDb, String> {
+        let mut db = TuningDb::new(path);
+        if !path.is_file() {
+            return Ok(db);
+        }
+
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(error) => return Err(format!("error reading tuning database {}: {}", path.display(), error)),
+        };
+
+        for (number, line) in content.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() != 7 {
+                return Err(format!("malformed tuning database line {}: {}", number + 1, line));
+            }
+
+            let parsed = (
+                fields[3].parse::<u32>(),
+                fields[4].parse::<usize>(),
+                fields[5].parse::<usize>(),
+                fields[6].parse::<u64>(),
+            );
+            let (size_bucket, local, elements_per_item, nanos) = match parsed {
+                (Ok(size_bucket), Ok(local), Ok(elements_per_item), Ok(nanos)) => {
+                    (size_bucket, local, elements_per_item, nanos)
+                }
+                _ => return Err(format!("malformed tuning database line {}: {}", number + 1, line)),
+            };
+
+            db.entries.insert(
+                TuningKey {
+                    device: fields[0].to_string(),
+                    kernel: fields[1].to_string(),
+                    options: fields[2].to_string(),
+                    size_bucket,
+                },
+                TuningEntry {
+                    local,
+                    elements_per_item,
+                    nanos,
+                },
+            );
+        }
+
+        Ok(db)
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let mut lines: Vec<String> = self
+            .entries
+            .iter()
+            .map(|(key, entry)| {
+                format!(
+                    "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                    sanitize(&key.device),
+                    sanitize(&key.kernel),
+                    sanitize(&key.options),
+                    key.size_bucket,
+                    entry.local,
+                    entry.elements_per_item,
+                    entry.nanos
+                )
+            })
+            .collect();
+        lines.sort();
+
+        let mut file = match fs::File::create(&self.path) {
+            Ok(file) => file,
+            Err(error) => return Err(format!("not able to create {}: {}", self.path.display(), error)),
+        };
+
+        let mut content = String::from("# device\tkernel\toptions\tsize bucket\tlocal\telements per item\tnanoseconds\n");
+        for line in lines {
+            content.push_str(&line);
+            content.push('\n');
+        }
+
+        match file.write_all(content.as_bytes()) {
+            Ok(_) => Ok(()),
+            Err(error) => Err(format!("not able to write {}: {}", self.path.display(), error)),
+        }
+    }
+
+    pub fn get(&self, key: &TuningKey) -> Option<TuningEntry> {
+        self.entries.get(key).copied()
+    }
+
+    pub fn insert(&mut self, key: TuningKey, entry: TuningEntry) {
+        self.entries.insert(key, entry);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+pub fn get_tuning_db_path() -> PathBuf {
+    match std::env::var(TUNING_DB_ENV) {
+        Ok(path) => PathBuf::from(path),
+        Err(_) => PathBuf::from(DEFAULT_TUNING_DB),
+    }
+}
+
+fn global_tuning_db() -> &'static Mutex<TuningDb> {
+    static DB: OnceLock<Mutex<TuningDb>> = OnceLock::new();
+    DB.get_or_init(|| {
+        let path = get_tuning_db_path();
+        match TuningDb::load(&path) {
+            Ok(db) => Mutex::new(db),
+            Err(error) => {
+                debug!("{}", error);
+                Mutex::new(TuningDb::new(&path))
+            }
+        }
+    })
+}
+
+pub fn has_entries() -> bool {
+    match global_tuning_db().lock() {
+        Ok(db) => !db.is_empty(),
+        Err(_) => false,
+    }
+}
+
+pub fn lookup(key: &TuningKey) -> Option<TuningEntry> {
+    match global_tuning_db().lock() {
+        Ok(db) => db.get(key),
+        Err(_) => None,
+    }
+}
+
+pub fn get_candidate_local_sizes(
+    queue: &command_queue::CommandQueue,
+    kernel: &kernel::Kernel,
+) -> Result<Vec<usize>, String> {
+    let device_id = match queue.device() {
+        Ok(device_id) => device_id,
+        Err(error) => return Err(format!("not able to get device of queue: {}", error)),
+    };
+
+    let limit = match kernel.get_work_group_size(device_id) {
+        Ok(limit) => limit,
+        Err(error) => return Err(format!("not able to get kernel work-group size: {}", error)),
+    };
+
+    let preferred = match kernel.get_work_group_size_multiple(device_id) {
+        Ok(preferred) => std::cmp::max(preferred, 1),
+        Err(error) => return Err(format!("not able to get preferred work-group size multiple: {}", error)),
+    };
+
+    let mut candidates = Vec::new();
+    let mut local = preferred;
+    while local <= limit {
+        candidates.push(local);
+        local *= 2;
+    }
+    if candidates.is_empty() {
+        candidates.push(std::cmp::max(limit, 1));
+    }
+
+    Ok(candidates)
+}
+
+// the kernel must already have its arguments bound, be safe to run repeatedly
+// and process its range with a grid-stride loop
+pub fn autotune(
+    queue: &command_queue::CommandQueue,
+    kernel: &kernel::Kernel,
+    elements: usize,
+    elements_per_item: &[usize],
+    repetitions: usize,
+) -> Result<TuningEntry, String> {
+    let local_sizes = match get_candidate_local_sizes(queue, kernel) {
+        Ok(local_sizes) => local_sizes,
+        Err(error) => return Err(error),
+    };
+
+    let mut best: Option<TuningEntry> = None;
+    for local in &local_sizes {
+        for ept in elements_per_item {
+            let config = exec::LocalSize::Strided {
+                local: *local,
+                elements_per_item: std::cmp::max(*ept, 1),
+            };
+
+            let mut fastest = u64::MAX;
+            for _ in 0..std::cmp::max(repetitions, 1) {
+                let event = match exec::enqueue_kernel(queue, kernel, elements, config, &events::EventList::new()) {
+                    Ok(event) => event,
+                    Err(error) => return Err(error),
+                };
+
+                match event.wait() {
+                    Ok(_) => (),
+                    Err(error) => return Err(format!("error waiting for kernel: {}", error)),
+                };
+
+                let start = match event.profiling_command_start() {
+                    Ok(start) => start,
+                    Err(error) => return Err(format!("not able to read profiling info: {}", error)),
+                };
+
+                let end = match event.profiling_command_end() {
+                    Ok(end) => end,
+                    Err(error) => return Err(format!("not able to read profiling info: {}", error)),
+                };
+
+                fastest = std::cmp::min(fastest, end.saturating_sub(start));
+            }
+
+            let candidate = TuningEntry {
+                local: *local,
+                elements_per_item: std::cmp::max(*ept, 1),
+                nanos: fastest,
+            };
+            best = match best {
+                Some(best) if best.nanos <= candidate.nanos => Some(best),
+                _ => Some(candidate),
+            };
+        }
+    }
+
+    match best {
+        Some(best) => Ok(best),
+        None => Err(String::from("no launch configuration candidates")),
+    }
+}
+
+pub fn autotune_and_store(
+    queue: &command_queue::CommandQueue,
+    kernel: &kernel::Kernel,
+    elements: usize,
+    elements_per_item: &[usize],
+    repetitions: usize,
+) -> Result<TuningEntry, String> {
+    let key = match get_tuning_key(queue, kernel, elements) {
+        Ok(key) => key,
+        Err(error) => return Err(error),
+    };
+
+    let best = match autotune(queue, kernel, elements, elements_per_item, repetitions) {
+        Ok(best) => best,
+        Err(error) => return Err(error),
+    };
+
+    let mut db = match global_tuning_db().lock() {
+        Ok(db) => db,
+        Err(error) => return Err(format!("tuning database lock poisoned: {}", error)),
+    };
+    db.insert(key, best);
+
+    match db.save() {
+        Ok(_) => Ok(best),
+        Err(error) => Err(error),
+    }
+}
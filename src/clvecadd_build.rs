@@ -0,0 +1,84 @@
+use std::path::PathBuf;
+
+use clvecadd_cargo::clvecadd::aot;
+use clvecadd_cargo::clvecadd::container;
+use clvecadd_cargo::clvecadd::setup;
+
+fn usage() -> String {
+    String::from("usage: clvecadd-build [--output <cache dir>] [--strict]")
+}
+
+fn main_impl() -> Result<(), String> {
+    let mut cache_dir = container::get_cache_dir();
+    let mut strict = false;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => match args.next() {
+                Some(path) => cache_dir = PathBuf::from(path),
+                None => return Err(usage()),
+            },
+            "--strict" => strict = true,
+            "-h" | "--help" => {
+                println!("{}", usage());
+                return Ok(());
+            }
+            _ => return Err(format!("unknown argument {}\n{}", arg, usage())),
+        };
+    }
+
+    let devices = match setup::get_all_devices(setup::DeviceType::All) {
+        Ok(devices) => devices,
+        Err(error) => return Err(error),
+    };
+    if devices.is_empty() {
+        return Err(String::from("no OpenCL devices found"));
+    }
+
+    let outcomes = match aot::build_all(&devices, &cache_dir) {
+        Ok(outcomes) => outcomes,
+        Err(error) => return Err(error),
+    };
+
+    let mut failed = 0;
+    for outcome in &outcomes {
+        match &outcome.result {
+            Ok(path) => println!("ok      {} {} on {}: {}", outcome.kernel, outcome.element_type, outcome.device, path.display()),
+            Err(error) => {
+                failed += 1;
+                println!("failed  {} {} on {}", outcome.kernel, outcome.element_type, outcome.device);
+                for line in error.lines() {
+                    println!("        {}", line);
+                }
+            }
+        };
+    }
+
+    for excluded in &aot::EXCLUDED_SOURCES {
+        println!("skipped {} ({}): {}", excluded.name, excluded.path, excluded.reason);
+    }
+
+    println!(
+        "{} of {} builds written to {}, {} failed",
+        outcomes.len() - failed,
+        outcomes.len(),
+        cache_dir.display(),
+        failed
+    );
+
+    if strict && failed > 0 {
+        return Err(format!("{} builds failed", failed));
+    }
+    Ok(())
+}
+
+fn main() -> Result<(), i32> {
+    match main_impl() {
+        Ok(_) => Ok(()),
+        Err(error) => {
+            eprintln!("{}", error);
+            Err(-1)
+        }
+    }
+}
@@ -3,12 +3,8 @@ use opencl3::memory;
 use opencl3::memory::ClMem;
 use opencl3::kernel;
 use opencl3::command_queue;
-use opencl3::program;
 use opencl3::event;
-use std::fs;
-use std::io::Write;
 use std::path::Path;
-use num_traits::NumOps;
 
 use log::error;
 use log::info;
@@ -17,37 +13,27 @@ use log4rs::append::console::ConsoleAppender;
 use log4rs::config::{Appender, Root};
 use log4rs::Config;
 
-pub mod clvecadd;
 pub mod test;
 
+use clvecadd_cargo::cl_kernel;
+use clvecadd_cargo::clvecadd;
+
 use crate::clvecadd::traits;
+use crate::clvecadd::arith;
 use crate::clvecadd::setup;
 use crate::clvecadd::buffer;
+use crate::clvecadd::caps;
 use crate::clvecadd::exec;
+use crate::clvecadd::future;
+use crate::clvecadd::graph;
+use crate::clvecadd::image;
+use crate::clvecadd::options;
+use crate::clvecadd::pool;
+use crate::clvecadd::profile;
+use crate::clvecadd::registry;
 
-pub fn create_binary(program: &program::Program, path_to_bin: String) -> Result<(), String> {
-    let bins = match program.get_binaries() {
-        Ok(bins) => bins,
-        Err(error) => return Err(format!("not able to get binaries: {}", error)),
-    };
-
-    let bin = match bins.first().ok_or_else(|| 0) {
-        Ok(bin) => bin,
-        Err(error) => return Err(format!("no programs built: {}", error)),
-    };
-
-    let mut file = match fs::File::create(&path_to_bin) {
-        Ok(file) => file,
-        Err(error) => return Err(format!("not able to create {}: {}", path_to_bin, error)),
-    };
-
-    match file.write(bin) {
-        Ok(_) => (),
-        Err(error) => return Err(format!("not able to write {}: {}", path_to_bin, error)),
-    };
-
-    Ok(())
-}
+cl_kernel!(pub fn add_vectors_inplace<T>(a: &mut [T], b: &[T], n: u64) = AddVectorsInplace);
+cl_kernel!(pub fn sub_vectors<T>(a: &[T], b: &[T], c: &mut [T], n: u64) = SubVectors);
 
 pub fn prepare_kernel_for_vecadd<T: traits::OpenclNum + traits::HasOpenclString>(
     context: &context::Context,
@@ -56,42 +42,9 @@ pub fn prepare_kernel_for_vecadd<T: traits::OpenclNum + traits::HasOpenclString>
     buffer_c: &memory::Buffer<T>,
     elements: usize,
 ) -> Result<kernel::Kernel, String> {
-    let kernel_name = String::from("addVectors");
-    let path_to_bin = String::from("src/bin/vecadd/vecadd.bin");
-    let path_to_source = String::from("src/opencl/vecadd/vecadd.cl");
-
-    let sources = [Path::new(&path_to_source)];
-    let binary = [Path::new(&path_to_bin)];
-
-    let mut options = String::from("-cl-std=CL3.0 -w -D ARRAY_TYPE=");
-    options.push_str(<T>::as_opencl_string());
-
-    let progs = match exec::create_and_build_from_binaries(&context, &binary, &options) {
-        Ok(progs) => progs,
-        Err(error) => {
-            debug!("{}", error);
-            match exec::create_and_build_from_sources(&context, &sources, &options) {
-                Ok(progs) => progs,
-                Err(error) => return Err(error),
-            }
-        }
-    };
-
-    let prog = match progs.first().ok_or_else(|| 0) {
-        Ok(prog) => prog,
-        Err(error) => return Err(format!("no programs built: {}", error)),
-    };
-
-    match create_binary(prog, path_to_bin) {
-        Ok(_) => (),
-        Err(error) => {
-            debug!("{}", error);
-        }
-    };
-
-    let kernel = match kernel::Kernel::create(prog, &kernel_name) {
+    let kernel = match registry::get_kernel::<T>(context, registry::Kernels::AddVectors) {
         Ok(kernel) => kernel,
-        Err(error) => return Err(format!("not able to get kernel: {}", error)),
+        Err(error) => return Err(error),
     };
 
     unsafe {
@@ -147,6 +100,194 @@ pub fn prepare_kernel_for_vecadd<T: traits::OpenclNum + traits::HasOpenclString>
     Ok(kernel)
 }
 
+#[allow(clippy::too_many_arguments)]
+fn enqueue_vecadd<T: Default + Copy + traits::OpenclNum + traits::HasOpenclString>(
+    context: &context::Context,
+    queue: &command_queue::CommandQueue,
+    buffer_a: &mut memory::Buffer<T>,
+    buffer_b: &mut memory::Buffer<T>,
+    buffer_c: &mut memory::Buffer<T>,
+    a: &mut Vec<T>,
+    b: &mut Vec<T>,
+    c: &mut Vec<T>,
+) -> Result<Vec<event::Event>, String> {
+    let size = c.len();
+
+    let kernel = match prepare_kernel_for_vecadd(context, buffer_a, buffer_b, buffer_c, size) {
+        Ok(kernel) => kernel,
+        Err(error) => return Err(error),
+    };
+
+    let mut graph = graph::TaskGraph::new();
+    graph.write(buffer_a, a);
+    graph.write(buffer_b, b);
+    graph.launch(&kernel, size, &[&*buffer_a, &*buffer_b], &[&*buffer_c]);
+    graph.read(buffer_c, c);
+
+    graph.submit(queue)
+}
+
+pub fn vecadd_pooled<T: Default + Copy + traits::OpenclNum + traits::HasOpenclString>(
+    pool: &pool::BufferPool,
+    context: &context::Context,
+    queue: &command_queue::CommandQueue,
+    a: &mut Vec<T>,
+    b: &mut Vec<T>,
+) -> Result<Vec<T>, String> {
+    let size = std::cmp::min(a.len(), b.len());
+    let mut c: Vec<T> = Vec::new();
+    c.resize_with(size, || <T>::default());
+
+    // OpenCL rejects zero sized buffers and there is nothing to add
+    if size == 0 {
+        return Ok(c);
+    }
+
+    let mut buffer_a: pool::PooledBuffer<T> = match pool.acquire(context, a.len(), buffer::MemMode::Read) {
+        Ok(buffer_a) => buffer_a,
+        Err(error) => return Err(error),
+    };
+
+    let mut buffer_b: pool::PooledBuffer<T> = match pool.acquire(context, b.len(), buffer::MemMode::Read) {
+        Ok(buffer_b) => buffer_b,
+        Err(error) => return Err(error),
+    };
+
+    let mut buffer_c: pool::PooledBuffer<T> = match pool.acquire(context, size, buffer::MemMode::Write) {
+        Ok(buffer_c) => buffer_c,
+        Err(error) => return Err(error),
+    };
+
+    match enqueue_vecadd(context, queue, &mut buffer_a, &mut buffer_b, &mut buffer_c, a, b, &mut c) {
+        Ok(_) => (),
+        Err(error) => return Err(error),
+    };
+
+    match queue.finish() {
+        Ok(_) => (),
+        Err(error) => return Err(format!("cannot finish queue: {}", error)),
+    }
+
+    Ok(c)
+}
+
+pub async fn vecadd_async<T: Default + Copy + traits::OpenclNum + traits::HasOpenclString>(
+    context: &context::Context,
+    queue: &command_queue::CommandQueue,
+    a: &mut Vec<T>,
+    b: &mut Vec<T>,
+) -> Result<Vec<T>, String> {
+    let size = std::cmp::min(a.len(), b.len());
+    let mut c: Vec<T> = Vec::new();
+    c.resize_with(size, || <T>::default());
+
+    // OpenCL rejects zero sized buffers and there is nothing to add
+    if size == 0 {
+        return Ok(c);
+    }
+
+    let mut buffer_a: memory::Buffer<T> = match buffer::create_buffer(context, a, buffer::MemMode::Read) {
+        Ok(buffer_a) => buffer_a,
+        Err(error) => return Err(error),
+    };
+
+    let mut buffer_b: memory::Buffer<T> = match buffer::create_buffer(context, b, buffer::MemMode::Read) {
+        Ok(buffer_b) => buffer_b,
+        Err(error) => return Err(error),
+    };
+
+    let mut buffer_c: memory::Buffer<T> = match buffer::create_buffer(context, &mut c, buffer::MemMode::Write) {
+        Ok(buffer_c) => buffer_c,
+        Err(error) => return Err(error),
+    };
+
+    let read_event = match enqueue_vecadd(context, queue, &mut buffer_a, &mut buffer_b, &mut buffer_c, a, b, &mut c) {
+        Ok(mut submitted) => match submitted.pop() {
+            Some(read_event) => read_event,
+            None => return Err(String::from("no read event submitted")),
+        },
+        Err(error) => return Err(error),
+    };
+
+    let read_future = match future::EventFuture::new(read_event) {
+        Ok(read_future) => read_future,
+        Err(error) => {
+            let _ = queue.finish();
+            return Err(error);
+        }
+    };
+
+    match queue.flush() {
+        Ok(_) => (),
+        Err(error) => return Err(format!("cannot flush queue: {}", error)),
+    }
+
+    match read_future.await {
+        Ok(_) => (),
+        Err(error) => return Err(error),
+    };
+
+    Ok(c)
+}
+
+pub fn vecadd_with_report<T: Default + Copy + traits::OpenclNum + traits::HasOpenclString>(
+    context: &context::Context,
+    queue: &command_queue::CommandQueue,
+    a: &mut Vec<T>,
+    b: &mut Vec<T>,
+) -> Result<(Vec<T>, profile::VecAddReport), String> {
+    let size = std::cmp::min(a.len(), b.len());
+    let mut c: Vec<T> = Vec::new();
+    c.resize_with(size, || <T>::default());
+
+    // OpenCL rejects zero sized buffers and there is nothing to add
+    if size == 0 {
+        return Ok((c, profile::VecAddReport::default()));
+    }
+
+    let mut buffer_a: memory::Buffer<T> = match buffer::create_buffer(context, a, buffer::MemMode::Read) {
+        Ok(buffer_a) => buffer_a,
+        Err(error) => return Err(error),
+    };
+
+    let mut buffer_b: memory::Buffer<T> = match buffer::create_buffer(context, b, buffer::MemMode::Read) {
+        Ok(buffer_b) => buffer_b,
+        Err(error) => return Err(error),
+    };
+
+    let mut buffer_c: memory::Buffer<T> = match buffer::create_buffer(context, &mut c, buffer::MemMode::Write) {
+        Ok(buffer_c) => buffer_c,
+        Err(error) => return Err(error),
+    };
+
+    let submitted = match enqueue_vecadd(context, queue, &mut buffer_a, &mut buffer_b, &mut buffer_c, a, b, &mut c) {
+        Ok(submitted) => submitted,
+        Err(error) => return Err(error),
+    };
+
+    match queue.finish() {
+        Ok(_) => (),
+        Err(error) => return Err(format!("cannot finish queue: {}", error)),
+    }
+
+    let timings = match profile::get_timings(&submitted) {
+        Ok(timings) => timings,
+        Err(error) => return Err(error),
+    };
+
+    let element_size = std::mem::size_of::<T>();
+    let report = profile::VecAddReport {
+        uploads: timings[..2].to_vec(),
+        kernel: timings[2],
+        download: timings[3],
+        bytes_uploaded: (a.len() + b.len()) * element_size,
+        bytes_downloaded: size * element_size,
+        bytes_processed: 3 * size * element_size,
+    };
+
+    Ok((c, report))
+}
+
 pub fn vecadd<T: Default + Copy + traits::OpenclNum + traits::HasOpenclString>(
     context: &context::Context,
     queue: &command_queue::CommandQueue,
@@ -157,6 +298,11 @@ pub fn vecadd<T: Default + Copy + traits::OpenclNum + traits::HasOpenclString>(
     let mut c: Vec<T> = Vec::new();
     c.resize_with(size, || <T>::default());
 
+    // OpenCL rejects zero sized buffers and there is nothing to add
+    if size == 0 {
+        return Ok(c);
+    }
+
     let mut buffer_a: memory::Buffer<T> = match buffer::create_buffer(&context, a, buffer::MemMode::Read) {
         Ok(buffer_a) => buffer_a,
         Err(error) => return Err(error),
@@ -172,32 +318,130 @@ pub fn vecadd<T: Default + Copy + traits::OpenclNum + traits::HasOpenclString>(
         Err(error) => return Err(error),
     };
 
-    let kernel = match prepare_kernel_for_vecadd(&context, &buffer_a, &buffer_b, &buffer_c, size) {
+    match enqueue_vecadd(context, queue, &mut buffer_a, &mut buffer_b, &mut buffer_c, a, b, &mut c) {
+        Ok(_) => (),
+        Err(error) => return Err(error),
+    };
+
+    match queue.finish() {
+        Ok(_) => (),
+        Err(error) => return Err(format!("cannot finish queue: {}", error)),
+    }
+
+    Ok(c)
+}
+
+pub fn prepare_kernel_for_image_op<T: traits::HasImageFormat>(
+    context: &context::Context,
+    kernel_name: &str,
+    image_a: &memory::Image,
+    image_b: &memory::Image,
+    image_c: &memory::Image,
+    sampler: &memory::Sampler,
+) -> Result<kernel::Kernel, String> {
+    let path_to_source = String::from("src/opencl/image/imageops.cl");
+    let sources = [Path::new(&path_to_source)];
+
+    let options = match options::BuildOptions::new().detect_standard(context) {
+        Ok(build_options) => build_options
+            .define("IMAGE_SUFFIX", <T>::image_access_suffix())
+            .define("PIXEL_TYPE", image::get_pixel_type::<T>())
+            .to_string(),
+        Err(error) => return Err(error),
+    };
+
+    let progs = match exec::create_and_build_from_sources(context, &sources, &options) {
+        Ok(progs) => progs,
+        Err(error) => return Err(error),
+    };
+
+    let prog = match progs.first().ok_or_else(|| 0) {
+        Ok(prog) => prog,
+        Err(error) => return Err(format!("no programs built: {}", error)),
+    };
+
+    let kernel = match kernel::Kernel::create(prog, kernel_name) {
         Ok(kernel) => kernel,
+        Err(error) => return Err(format!("not able to get kernel: {}", error)),
+    };
+
+    unsafe {
+        let args = [image_a.get(), image_b.get(), image_c.get()];
+        for (arg, value) in args.iter().enumerate() {
+            match kernel.set_arg(arg as u32, value) {
+                Ok(_) => (),
+                Err(error) => {
+                    return Err(format!(
+                        "error setting kernel argument {}: {}",
+                        arg + 1,
+                        error
+                    ))
+                }
+            }
+        }
+
+        match kernel.set_arg(3, &sampler.get()) {
+            Ok(_) => (),
+            Err(error) => return Err(format!("error setting kernel argument 4: {}", error)),
+        }
+    }
+
+    Ok(kernel)
+}
+
+pub fn image_op<T: Default + Copy + traits::HasImageFormat>(
+    context: &context::Context,
+    queue: &command_queue::CommandQueue,
+    op: image::ImageOp,
+    a: &mut Vec<T>,
+    b: &mut Vec<T>,
+    region: [usize; 3],
+) -> Result<Vec<T>, String> {
+    let mut c: Vec<T> = Vec::new();
+    c.resize_with(a.len(), || <T>::default());
+
+    let mut image_a = match image::create_image(context, a, region, buffer::MemMode::Read) {
+        Ok(image_a) => image_a,
+        Err(error) => return Err(error),
+    };
+
+    let mut image_b = match image::create_image(context, b, region, buffer::MemMode::Read) {
+        Ok(image_b) => image_b,
         Err(error) => return Err(error),
     };
 
-    let mut wait_list: Vec<event::Event> = Vec::new();
-    let write_a_event = match buffer::write_buffer(&queue, &mut buffer_a, a, &wait_list) {
+    let image_c = match image::create_image(context, &mut c, region, buffer::MemMode::Write) {
+        Ok(image_c) => image_c,
+        Err(error) => return Err(error),
+    };
+
+    let sampler = match image::SamplerBuilder::new().build(context) {
+        Ok(sampler) => sampler,
+        Err(error) => return Err(error),
+    };
+
+    let kernel_name = image::get_kernel_name(op, region);
+    let kernel = match prepare_kernel_for_image_op::<T>(context, kernel_name, &image_a, &image_b, &image_c, &sampler) {
+        Ok(kernel) => kernel,
+        Err(error) => return Err(error),
+    };
+
+    let write_a_event = match image::write_image(queue, &mut image_a, a, []) {
         Ok(write_a_event) => write_a_event,
         Err(error) => return Err(error),
     };
 
-    let write_b_event = match buffer::write_buffer(&queue, &mut buffer_b, b, &wait_list) {
+    let write_b_event = match image::write_image(queue, &mut image_b, b, []) {
         Ok(write_b_event) => write_b_event,
         Err(error) => return Err(error),
     };
 
-    wait_list.push(write_a_event);
-    wait_list.push(write_b_event);
-    let execute_event = match exec::execute_kernel(&queue, &kernel, size, &wait_list) {
+    let execute_event = match exec::execute_kernel_over_region(queue, &kernel, region, [write_a_event, write_b_event]) {
         Ok(execute_event) => execute_event,
         Err(error) => return Err(error),
     };
-    wait_list.clear();
 
-    wait_list.push(execute_event);
-    let _read_event = match buffer::read_buffer(&queue, &mut buffer_c, &mut c, &wait_list) {
+    let _read_event = match image::read_image(queue, &image_c, &mut c, [execute_event]) {
         Ok(read_event) => read_event,
         Err(error) => return Err(error),
     };
@@ -210,14 +454,25 @@ pub fn vecadd<T: Default + Copy + traits::OpenclNum + traits::HasOpenclString>(
     Ok(c)
 }
 
-pub fn vecadd_cpu<T: Copy + Default + NumOps>(a: &Vec<T>, b: &Vec<T>) -> Result<Vec<T>, String> {
-    let size = std::cmp::min(a.len(), b.len());
-    let mut c: Vec<T> = Vec::new();
-    c.resize_with(size, || <T>::default());
-
-    c = (0..size).map(|i| a[i] + b[i]).collect();
+pub fn vecadd_with_cpu_fallback<T: Default + Copy + arith::ArithNum + traits::OpenclNum + traits::HasOpenclString>(
+    context: &context::Context,
+    queue: &command_queue::CommandQueue,
+    a: &mut Vec<T>,
+    b: &mut Vec<T>,
+) -> Result<Vec<T>, String> {
+    match caps::check_type_support::<T>(context) {
+        Ok(_) => vecadd(context, queue, a, b),
+        Err(caps::TypeSupportError::Unsupported(unsupported)) => {
+            info!("{}, falling back to cpu...", unsupported);
+            vecadd_cpu(a, b)
+        }
+        Err(caps::TypeSupportError::Query(error)) => Err(error),
+    }
+}
 
-    Ok(c)
+// wraps on integer overflow like addVectors, see arith::overflow_op_cpu for the other modes
+pub fn vecadd_cpu<T: Copy + Default + arith::ArithNum>(a: &Vec<T>, b: &Vec<T>) -> Result<Vec<T>, String> {
+    arith::binary_op_cpu(arith::BinaryOp::Add, a, b)
 }
 
 fn main_impl() -> Result<(), String> {
@@ -245,6 +500,7 @@ fn main_impl() -> Result<(), String> {
         }
     };
 
+    registry::global_registry().remove_context(&ctx);
     Ok(())
 }
 
@@ -0,0 +1,36 @@
+#ifndef IMAGE_SUFFIX
+//#warning "image access type not specified, defaulting to float"
+#define IMAGE_SUFFIX f
+#define PIXEL_TYPE float4
+#endif
+
+#pragma OPENCL EXTENSION cl_khr_3d_image_writes: enable
+
+#define CONCAT(a, b) a##b
+#define EXPAND_CONCAT(a, b) CONCAT(a, b)
+#define READ_IMAGE EXPAND_CONCAT(read_image, IMAGE_SUFFIX)
+#define WRITE_IMAGE EXPAND_CONCAT(write_image, IMAGE_SUFFIX)
+
+__kernel void addImages2d(__read_only image2d_t a, __read_only image2d_t b, __write_only image2d_t c, sampler_t sampler) {
+  int2 pos = (int2)(get_global_id(0), get_global_id(1));
+  PIXEL_TYPE value = READ_IMAGE(a, sampler, pos) + READ_IMAGE(b, sampler, pos);
+  WRITE_IMAGE(c, pos, value);
+}
+
+__kernel void subImages2d(__read_only image2d_t a, __read_only image2d_t b, __write_only image2d_t c, sampler_t sampler) {
+  int2 pos = (int2)(get_global_id(0), get_global_id(1));
+  PIXEL_TYPE value = READ_IMAGE(a, sampler, pos) - READ_IMAGE(b, sampler, pos);
+  WRITE_IMAGE(c, pos, value);
+}
+
+__kernel void addImages3d(__read_only image3d_t a, __read_only image3d_t b, __write_only image3d_t c, sampler_t sampler) {
+  int4 pos = (int4)(get_global_id(0), get_global_id(1), get_global_id(2), 0);
+  PIXEL_TYPE value = READ_IMAGE(a, sampler, pos) + READ_IMAGE(b, sampler, pos);
+  WRITE_IMAGE(c, pos, value);
+}
+
+__kernel void subImages3d(__read_only image3d_t a, __read_only image3d_t b, __write_only image3d_t c, sampler_t sampler) {
+  int4 pos = (int4)(get_global_id(0), get_global_id(1), get_global_id(2), 0);
+  PIXEL_TYPE value = READ_IMAGE(a, sampler, pos) - READ_IMAGE(b, sampler, pos);
+  WRITE_IMAGE(c, pos, value);
+}
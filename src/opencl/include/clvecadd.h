@@ -0,0 +1,7 @@
+#ifndef CLVECADD_H
+#define CLVECADD_H
+
+#define FOR_EACH_GID(gid, num) \
+  for(unsigned long long int gid = get_global_id(0); gid < (num); gid += get_global_size(0))
+
+#endif
@@ -1,30 +1,201 @@
 #ifndef ARRAY_TYPE
 //#warning "type of vector elements not specified, defaulting to float"
 #define ARRAY_TYPE float
+#define ARRAY_TYPE_IS_FLOAT
 #endif
 
+#ifdef cl_khr_fp16
 #pragma OPENCL EXTENSION cl_khr_fp16: enable
+#endif
+
+#ifdef cl_khr_fp64
+#pragma OPENCL EXTENSION cl_khr_fp64: enable
+#endif
+
+// integer arithmetic goes through ulong so overflow wraps like the cpu versions,
+// division by zero gives zero and the remainder by zero gives the dividend
+#ifdef ARRAY_TYPE_IS_FLOAT
+#define ADD(a, b) ((a) + (b))
+#define SUB(a, b) ((a) - (b))
+#define MUL(a, b) ((a) * (b))
+#define DIV(a, b) ((a) / (b))
+#define REM(a, b) fmod(a, b)
+#define MIN(a, b) fmin(a, b)
+#define MAX(a, b) fmax(a, b)
+#define ABS(a) fabs(a)
+#define NEG(a) (-(a))
+#else
+#define IS_SIGNED ((ARRAY_TYPE)-1 < (ARRAY_TYPE)0)
+#define ADD(a, b) ((ARRAY_TYPE)((ulong)(a) + (ulong)(b)))
+#define SUB(a, b) ((ARRAY_TYPE)((ulong)(a) - (ulong)(b)))
+#define MUL(a, b) ((ARRAY_TYPE)((ulong)(a) * (ulong)(b)))
+#define DIV(a, b) ((b) == 0 ? (ARRAY_TYPE)0 : (IS_SIGNED && (b) == (ARRAY_TYPE)-1) ? NEG(a) : (ARRAY_TYPE)((a) / (b)))
+#define REM(a, b) ((b) == 0 ? (a) : (IS_SIGNED && (b) == (ARRAY_TYPE)-1) ? (ARRAY_TYPE)0 : (ARRAY_TYPE)((a) % (b)))
+#define MIN(a, b) min(a, b)
+#define MAX(a, b) max(a, b)
+#define ABS(a) ((ARRAY_TYPE)abs(a))
+#define NEG(a) ((ARRAY_TYPE)(0UL - (ulong)(a)))
+#endif
 
 __kernel void addVectors(__global const ARRAY_TYPE *a, __global const ARRAY_TYPE *b, __global ARRAY_TYPE *c, ulong num) {
-  unsigned long long int gid = get_global_id(0);
-  if(gid < num)
-    c[gid] = a[gid] + b[gid];
+  for(unsigned long long int gid = get_global_id(0); gid < num; gid += get_global_size(0))
+    c[gid] = ADD(a[gid], b[gid]);
 }
 
 __kernel void addVectorsInplace(__global ARRAY_TYPE *a, __global const ARRAY_TYPE *b, ulong num) {
-  unsigned long long int gid = get_global_id(0);
-  if(gid < num)
-    a[gid] += b[gid];
+  for(unsigned long long int gid = get_global_id(0); gid < num; gid += get_global_size(0))
+    a[gid] = ADD(a[gid], b[gid]);
 }
 
 __kernel void subVectors(__global const ARRAY_TYPE *a, __global const ARRAY_TYPE *b, __global ARRAY_TYPE *c, ulong num) {
-  unsigned long long int gid = get_global_id(0);
-  if(gid < num)
-    c[gid] = a[gid] - b[gid];
+  for(unsigned long long int gid = get_global_id(0); gid < num; gid += get_global_size(0))
+    c[gid] = SUB(a[gid], b[gid]);
 }
 
 __kernel void subVectorsInplace(__global ARRAY_TYPE *a, __global const ARRAY_TYPE *b, ulong num) {
-  unsigned long long int gid = get_global_id(0);
-  if(gid < num)
-    a[gid] -= b[gid];
-}
\ No newline at end of file
+  for(unsigned long long int gid = get_global_id(0); gid < num; gid += get_global_size(0))
+    a[gid] = SUB(a[gid], b[gid]);
+}
+
+__kernel void mulVectors(__global const ARRAY_TYPE *a, __global const ARRAY_TYPE *b, __global ARRAY_TYPE *c, ulong num) {
+  for(unsigned long long int gid = get_global_id(0); gid < num; gid += get_global_size(0))
+    c[gid] = MUL(a[gid], b[gid]);
+}
+
+__kernel void divVectors(__global const ARRAY_TYPE *a, __global const ARRAY_TYPE *b, __global ARRAY_TYPE *c, ulong num) {
+  for(unsigned long long int gid = get_global_id(0); gid < num; gid += get_global_size(0))
+    c[gid] = DIV(a[gid], b[gid]);
+}
+
+__kernel void remVectors(__global const ARRAY_TYPE *a, __global const ARRAY_TYPE *b, __global ARRAY_TYPE *c, ulong num) {
+  for(unsigned long long int gid = get_global_id(0); gid < num; gid += get_global_size(0))
+    c[gid] = REM(a[gid], b[gid]);
+}
+
+__kernel void minVectors(__global const ARRAY_TYPE *a, __global const ARRAY_TYPE *b, __global ARRAY_TYPE *c, ulong num) {
+  for(unsigned long long int gid = get_global_id(0); gid < num; gid += get_global_size(0))
+    c[gid] = MIN(a[gid], b[gid]);
+}
+
+__kernel void maxVectors(__global const ARRAY_TYPE *a, __global const ARRAY_TYPE *b, __global ARRAY_TYPE *c, ulong num) {
+  for(unsigned long long int gid = get_global_id(0); gid < num; gid += get_global_size(0))
+    c[gid] = MAX(a[gid], b[gid]);
+}
+
+__kernel void absVector(__global const ARRAY_TYPE *a, __global ARRAY_TYPE *c, ulong num) {
+  for(unsigned long long int gid = get_global_id(0); gid < num; gid += get_global_size(0))
+    c[gid] = ABS(a[gid]);
+}
+
+__kernel void negVector(__global const ARRAY_TYPE *a, __global ARRAY_TYPE *c, ulong num) {
+  for(unsigned long long int gid = get_global_id(0); gid < num; gid += get_global_size(0))
+    c[gid] = NEG(a[gid]);
+}
+
+__kernel void scaleVector(const ARRAY_TYPE alpha, __global const ARRAY_TYPE *x, __global ARRAY_TYPE *c, ulong num) {
+  for(unsigned long long int gid = get_global_id(0); gid < num; gid += get_global_size(0))
+    c[gid] = MUL(alpha, x[gid]);
+}
+
+__kernel void addScalar(__global const ARRAY_TYPE *x, const ARRAY_TYPE s, __global ARRAY_TYPE *c, ulong num) {
+  for(unsigned long long int gid = get_global_id(0); gid < num; gid += get_global_size(0))
+    c[gid] = ADD(x[gid], s);
+}
+
+__kernel void axpy(const ARRAY_TYPE alpha, __global const ARRAY_TYPE *x, __global ARRAY_TYPE *y, ulong num) {
+  for(unsigned long long int gid = get_global_id(0); gid < num; gid += get_global_size(0))
+    y[gid] = ADD(MUL(alpha, x[gid]), y[gid]);
+}
+
+__kernel void axpby(const ARRAY_TYPE alpha, __global const ARRAY_TYPE *x, const ARRAY_TYPE beta, __global ARRAY_TYPE *y, ulong num) {
+  for(unsigned long long int gid = get_global_id(0); gid < num; gid += get_global_size(0))
+    y[gid] = ADD(MUL(alpha, x[gid]), MUL(beta, y[gid]));
+}
+
+#ifndef ARRAY_TYPE_IS_FLOAT
+__kernel void addVectorsSat(__global const ARRAY_TYPE *a, __global const ARRAY_TYPE *b, __global ARRAY_TYPE *c, ulong num) {
+  for(unsigned long long int gid = get_global_id(0); gid < num; gid += get_global_size(0))
+    c[gid] = add_sat(a[gid], b[gid]);
+}
+
+__kernel void subVectorsSat(__global const ARRAY_TYPE *a, __global const ARRAY_TYPE *b, __global ARRAY_TYPE *c, ulong num) {
+  for(unsigned long long int gid = get_global_id(0); gid < num; gid += get_global_size(0))
+    c[gid] = sub_sat(a[gid], b[gid]);
+}
+
+// the saturated result only differs from the wrapped one when the operation overflowed
+__kernel void addVectorsChecked(__global const ARRAY_TYPE *a, __global const ARRAY_TYPE *b, __global ARRAY_TYPE *c, __global uint *overflow, ulong num) {
+  for(unsigned long long int gid = get_global_id(0); gid < num; gid += get_global_size(0)) {
+    c[gid] = ADD(a[gid], b[gid]);
+    if (c[gid] != add_sat(a[gid], b[gid]))
+      *overflow = 1;
+  }
+}
+
+__kernel void subVectorsChecked(__global const ARRAY_TYPE *a, __global const ARRAY_TYPE *b, __global ARRAY_TYPE *c, __global uint *overflow, ulong num) {
+  for(unsigned long long int gid = get_global_id(0); gid < num; gid += get_global_size(0)) {
+    c[gid] = SUB(a[gid], b[gid]);
+    if (c[gid] != sub_sat(a[gid], b[gid]))
+      *overflow = 1;
+  }
+}
+#endif
+
+#ifdef ARRAY_TYPE_IS_FLOAT
+__kernel void sqrtVector(__global const ARRAY_TYPE *a, __global ARRAY_TYPE *c, ulong num) {
+  for(unsigned long long int gid = get_global_id(0); gid < num; gid += get_global_size(0))
+    c[gid] = sqrt(a[gid]);
+}
+
+__kernel void expVector(__global const ARRAY_TYPE *a, __global ARRAY_TYPE *c, ulong num) {
+  for(unsigned long long int gid = get_global_id(0); gid < num; gid += get_global_size(0))
+    c[gid] = exp(a[gid]);
+}
+
+__kernel void logVector(__global const ARRAY_TYPE *a, __global ARRAY_TYPE *c, ulong num) {
+  for(unsigned long long int gid = get_global_id(0); gid < num; gid += get_global_size(0))
+    c[gid] = log(a[gid]);
+}
+
+__kernel void sinVector(__global const ARRAY_TYPE *a, __global ARRAY_TYPE *c, ulong num) {
+  for(unsigned long long int gid = get_global_id(0); gid < num; gid += get_global_size(0))
+    c[gid] = sin(a[gid]);
+}
+
+__kernel void powVectors(__global const ARRAY_TYPE *a, __global const ARRAY_TYPE *b, __global ARRAY_TYPE *c, ulong num) {
+  for(unsigned long long int gid = get_global_id(0); gid < num; gid += get_global_size(0))
+    c[gid] = pow(a[gid], b[gid]);
+}
+#endif
+
+// masks are one uchar per element, bool is not allowed in kernel arguments
+__kernel void eqVectors(__global const ARRAY_TYPE *a, __global const ARRAY_TYPE *b, __global uchar *mask, ulong num) {
+  for(unsigned long long int gid = get_global_id(0); gid < num; gid += get_global_size(0))
+    mask[gid] = a[gid] == b[gid] ? 1 : 0;
+}
+
+__kernel void ltVectors(__global const ARRAY_TYPE *a, __global const ARRAY_TYPE *b, __global uchar *mask, ulong num) {
+  for(unsigned long long int gid = get_global_id(0); gid < num; gid += get_global_size(0))
+    mask[gid] = a[gid] < b[gid] ? 1 : 0;
+}
+
+__kernel void gtVectors(__global const ARRAY_TYPE *a, __global const ARRAY_TYPE *b, __global uchar *mask, ulong num) {
+  for(unsigned long long int gid = get_global_id(0); gid < num; gid += get_global_size(0))
+    mask[gid] = a[gid] > b[gid] ? 1 : 0;
+}
+
+__kernel void leVectors(__global const ARRAY_TYPE *a, __global const ARRAY_TYPE *b, __global uchar *mask, ulong num) {
+  for(unsigned long long int gid = get_global_id(0); gid < num; gid += get_global_size(0))
+    mask[gid] = a[gid] <= b[gid] ? 1 : 0;
+}
+
+__kernel void geVectors(__global const ARRAY_TYPE *a, __global const ARRAY_TYPE *b, __global uchar *mask, ulong num) {
+  for(unsigned long long int gid = get_global_id(0); gid < num; gid += get_global_size(0))
+    mask[gid] = a[gid] >= b[gid] ? 1 : 0;
+}
+
+// any non-zero mask entry picks a, unlike the select() built-in which looks at the top bit
+__kernel void selectVectors(__global const uchar *mask, __global const ARRAY_TYPE *a, __global const ARRAY_TYPE *b, __global ARRAY_TYPE *c, ulong num) {
+  for(unsigned long long int gid = get_global_id(0); gid < num; gid += get_global_size(0))
+    c[gid] = mask[gid] ? a[gid] : b[gid];
+}
@@ -1,5 +1,25 @@
 #[cfg(test)]
 mod clvecadd_test {
+    struct ThreadWaker(std::thread::Thread);
+
+    impl std::task::Wake for ThreadWaker {
+        fn wake(self: std::sync::Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        let waker = std::task::Waker::from(std::sync::Arc::new(ThreadWaker(std::thread::current())));
+        let mut context = std::task::Context::from_waker(&waker);
+        let mut future = std::pin::pin!(future);
+        loop {
+            match future.as_mut().poll(&mut context) {
+                std::task::Poll::Ready(output) => return output,
+                std::task::Poll::Pending => std::thread::park(),
+            }
+        }
+    }
+
     #[test]
     fn gpu_found() -> Result<(), String> {
         match crate::clvecadd::setup::get_all_gpus() {
@@ -83,4 +103,1330 @@ mod clvecadd_test {
             }
         };
     }
+
+    #[test]
+    fn size_classes_round_up() {
+        assert_eq!(crate::clvecadd::pool::get_size_class(1), 256);
+        assert_eq!(crate::clvecadd::pool::get_size_class(256), 256);
+        assert_eq!(crate::clvecadd::pool::get_size_class(257), 512);
+        assert_eq!(crate::clvecadd::pool::get_size_class(5000), 8192);
+    }
+
+    #[test]
+    fn perform_vecadd_with_pool() -> Result<(), String> {
+        let devices: Vec<opencl3::device::Device>;
+        let ctx: opencl3::context::Context;
+        let queue: opencl3::command_queue::CommandQueue;
+        unsafe {
+            devices = crate::clvecadd::setup::get_all_gpus().unwrap_unchecked();
+            (ctx, queue) = crate::clvecadd::setup::get_context_for_device(devices, 0).unwrap_unchecked();
+        }
+
+        let pool = crate::clvecadd::pool::BufferPool::new(1 << 20);
+        let mut a: Vec<i32> = vec![1, 2, 3, 4, 5];
+        let mut b: Vec<i32> = vec![6, 7, 8, 9, 10, 11];
+        let desired_outcome: Vec<i32> = vec![7, 9, 11, 13, 15];
+        for _ in 0..2 {
+            match crate::vecadd_pooled(&pool, &ctx, &queue, &mut a, &mut b) {
+                Ok(c) => assert_eq!(c, desired_outcome),
+                Err(error) => return Err(error),
+            };
+        }
+
+        let stats = pool.stats();
+        assert_eq!(stats.misses, 3);
+        assert_eq!(stats.hits, 3);
+        assert_eq!(stats.bytes_in_use, 0);
+        assert_eq!(stats.bytes_held, 3 * 256);
+
+        pool.clear();
+        assert_eq!(pool.stats().bytes_held, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn image_formats_follow_element_type() {
+        use crate::clvecadd::traits::HasImageFormat;
+
+        assert_eq!(<f32>::image_format().image_channel_order, opencl3::memory::CL_R);
+        assert_eq!(<f32>::image_format().image_channel_data_type, opencl3::memory::CL_FLOAT);
+        assert_eq!(<[u8; 4]>::image_format().image_channel_order, opencl3::memory::CL_RGBA);
+        assert_eq!(<[u8; 4]>::image_format().image_channel_data_type, opencl3::memory::CL_UNSIGNED_INT8);
+        assert_eq!(crate::clvecadd::image::get_pixel_type::<i16>(), "int4");
+        assert_eq!(
+            crate::clvecadd::image::get_kernel_name(crate::clvecadd::image::ImageOp::Sub, [4, 4, 2]),
+            "subImages3d"
+        );
+    }
+
+    #[test]
+    fn perform_image_add_on_gpu() -> Result<(), String> {
+        let devices: Vec<opencl3::device::Device>;
+        let ctx: opencl3::context::Context;
+        let queue: opencl3::command_queue::CommandQueue;
+        unsafe {
+            devices = crate::clvecadd::setup::get_all_gpus().unwrap_unchecked();
+            (ctx, queue) = crate::clvecadd::setup::get_context_for_device(devices, 0).unwrap_unchecked();
+        }
+
+        let mut a: Vec<f32> = vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0];
+        let mut b: Vec<f32> = vec![6.0, 5.0, 4.0, 3.0, 2.0, 1.0];
+        let desired_outcome: Vec<f32> = vec![7.0; 6];
+        match crate::image_op(&ctx, &queue, crate::clvecadd::image::ImageOp::Add, &mut a, &mut b, [3, 2, 1]) {
+            Ok(c) => {
+                assert_eq!(c, desired_outcome);
+                return Ok(());
+            },
+            Err(error) => return Err(error),
+        };
+    }
+
+    #[test]
+    fn empty_event_list_is_null() {
+        let events: Vec<opencl3::event::Event> = Vec::new();
+        let list = crate::clvecadd::events::EventList::from(&events);
+        let (count, ptr) = list.as_raw();
+        assert_eq!(count, 0);
+        assert!(ptr.is_null());
+
+        let merged = crate::clvecadd::events::EventList::new().merged(&list);
+        assert!(merged.is_empty());
+    }
+
+    #[test]
+    fn merge_event_lists() -> Result<(), String> {
+        let devices: Vec<opencl3::device::Device>;
+        let ctx: opencl3::context::Context;
+        let queue: opencl3::command_queue::CommandQueue;
+        unsafe {
+            devices = crate::clvecadd::setup::get_all_gpus().unwrap_unchecked();
+            (ctx, queue) = crate::clvecadd::setup::get_context_for_device(devices, 0).unwrap_unchecked();
+        }
+
+        let mut a: Vec<i32> = vec![1, 2, 3, 4, 5];
+        let mut buffer_a = match crate::clvecadd::buffer::create_buffer(&ctx, &mut a, crate::clvecadd::buffer::MemMode::Read) {
+            Ok(buffer_a) => buffer_a,
+            Err(error) => return Err(error),
+        };
+        let write_event = match crate::clvecadd::buffer::write_buffer(&queue, &mut buffer_a, &mut a, []) {
+            Ok(write_event) => write_event,
+            Err(error) => return Err(error),
+        };
+        let read_event = match crate::clvecadd::buffer::read_buffer(&queue, &mut buffer_a, &mut a, [write_event]) {
+            Ok(read_event) => read_event,
+            Err(error) => return Err(error),
+        };
+
+        let events = vec![read_event];
+        let mut list = crate::clvecadd::events::EventList::from(&events);
+        list.merge(&crate::clvecadd::events::EventList::from(&events));
+        assert_eq!(list.len(), 1);
+        assert!(!list.as_ptr().is_null());
+
+        match queue.finish() {
+            Ok(_) => return Ok(()),
+            Err(error) => return Err(format!("cannot finish queue: {}", error)),
+        };
+    }
+
+    #[test]
+    fn perform_vecadd_async_on_cpu_device() -> Result<(), String> {
+        let devices: Vec<opencl3::device::Device>;
+        let ctx: opencl3::context::Context;
+        let queue: opencl3::command_queue::CommandQueue;
+        unsafe {
+            devices = crate::clvecadd::setup::get_all_cpus().unwrap_unchecked();
+            (ctx, queue) = crate::clvecadd::setup::get_context_for_device(devices, 0).unwrap_unchecked();
+        }
+
+        let mut a: Vec<i32> = vec![1, 2, 3, 4, 5];
+        let mut b: Vec<i32> = vec![6, 7, 8, 9, 10, 11];
+        let desired_outcome: Vec<i32> = vec![7, 9, 11, 13, 15];
+        match block_on(crate::vecadd_async(&ctx, &queue, &mut a, &mut b)) {
+            Ok(c) => {
+                assert_eq!(c, desired_outcome);
+                return Ok(());
+            },
+            Err(error) => return Err(error),
+        };
+    }
+
+    #[test]
+    fn task_graph_derives_dependencies() -> Result<(), String> {
+        let devices: Vec<opencl3::device::Device>;
+        let ctx: opencl3::context::Context;
+        let queue: opencl3::command_queue::CommandQueue;
+        unsafe {
+            devices = crate::clvecadd::setup::get_all_gpus().unwrap_unchecked();
+            (ctx, queue) = crate::clvecadd::setup::get_context_for_device(devices, 0).unwrap_unchecked();
+        }
+
+        let mut a: Vec<i32> = vec![1, 2, 3, 4, 5];
+        let mut b: Vec<i32> = vec![6, 7, 8, 9, 10];
+        let mut c: Vec<i32> = vec![0; 5];
+        let a_again: Vec<i32> = vec![0; 5];
+        let buffer_a = match crate::clvecadd::buffer::create_buffer(&ctx, &mut a, crate::clvecadd::buffer::MemMode::Read) {
+            Ok(buffer) => buffer,
+            Err(error) => return Err(error),
+        };
+        let buffer_b = match crate::clvecadd::buffer::create_buffer(&ctx, &mut b, crate::clvecadd::buffer::MemMode::Read) {
+            Ok(buffer) => buffer,
+            Err(error) => return Err(error),
+        };
+        let buffer_c = match crate::clvecadd::buffer::create_buffer(&ctx, &mut c, crate::clvecadd::buffer::MemMode::Write) {
+            Ok(buffer) => buffer,
+            Err(error) => return Err(error),
+        };
+        let kernel = match crate::prepare_kernel_for_vecadd(&ctx, &buffer_a, &buffer_b, &buffer_c, 5) {
+            Ok(kernel) => kernel,
+            Err(error) => return Err(error),
+        };
+
+        let mut graph = crate::clvecadd::graph::TaskGraph::new();
+        let write_a = graph.write(&buffer_a, &a);
+        let write_b = graph.write(&buffer_b, &b);
+        let launch = graph.launch(&kernel, 5, &[&buffer_a, &buffer_b], &[&buffer_c]);
+        let overwrite_a = graph.write(&buffer_a, &a_again);
+        let read = graph.read(&buffer_c, &mut c);
+
+        assert_eq!(graph.dependencies(write_a), Some(&[][..]));
+        assert_eq!(graph.dependencies(launch), Some(&[write_a, write_b][..]));
+        assert_eq!(graph.dependencies(overwrite_a), Some(&[write_a, launch][..]));
+        assert_eq!(graph.dependencies(read), Some(&[launch][..]));
+        assert_eq!(graph.name(launch), Some("addVectors"));
+
+        match graph.submit(&queue) {
+            Ok(_) => (),
+            Err(error) => return Err(error),
+        };
+        match queue.finish() {
+            Ok(_) => (),
+            Err(error) => return Err(format!("cannot finish queue: {}", error)),
+        };
+
+        assert_eq!(c, vec![7, 9, 11, 13, 15]);
+        Ok(())
+    }
+
+    #[test]
+    fn local_work_size_respects_device_limits() {
+        use crate::clvecadd::exec::choose_local_work_size;
+
+        assert_eq!(choose_local_work_size(1024, 32, 100000), 256);
+        assert_eq!(choose_local_work_size(128, 32, 100000), 128);
+        assert_eq!(choose_local_work_size(100, 32, 100000), 96);
+        assert_eq!(choose_local_work_size(16, 32, 100000), 16);
+        assert_eq!(choose_local_work_size(1024, 32, 5), 32);
+        assert_eq!(crate::clvecadd::exec::get_global_work_size(96, 100), 192);
+        assert_eq!(choose_local_work_size(1024, 32, 0), 32);
+        assert_eq!(crate::clvecadd::exec::get_global_work_size(32, 0), 0);
+        assert_eq!(crate::clvecadd::exec::get_global_work_size(0, 5), 5);
+    }
+
+    #[test]
+    fn perform_empty_vecadd_on_gpu() -> Result<(), String> {
+        let devices: Vec<opencl3::device::Device>;
+        let ctx: opencl3::context::Context;
+        let queue: opencl3::command_queue::CommandQueue;
+        unsafe {
+            devices = crate::clvecadd::setup::get_all_gpus().unwrap_unchecked();
+            (ctx, queue) = crate::clvecadd::setup::get_context_for_device(devices, 0).unwrap_unchecked();
+        }
+
+        let mut a: Vec<i32> = Vec::new();
+        let mut b: Vec<i32> = vec![1, 2, 3];
+        match crate::vecadd(&ctx, &queue, &mut a, &mut b) {
+            Ok(c) => assert!(c.is_empty()),
+            Err(error) => return Err(error),
+        };
+
+        match crate::clvecadd::arith::binary_op(&ctx, &queue, crate::clvecadd::arith::BinaryOp::Mul, &a, &b) {
+            Ok(c) => assert!(c.is_empty()),
+            Err(error) => return Err(error),
+        };
+
+        Ok(())
+    }
+
+    #[test]
+    fn tunable_kernels_use_grid_stride_loops() {
+        // a tuned launch may start fewer work-items than elements
+        let stride = "gid < num; gid += get_global_size(0)";
+        let source = std::fs::read_to_string("src/opencl/vecadd/vecadd.cl").unwrap();
+        assert_eq!(source.matches("__kernel").count(), source.matches(stride).count());
+
+        let op = crate::clvecadd::elementwise::ElementwiseOp::new("fma", 3, "a * b + c");
+        assert!(op.generate_source().unwrap().contains(stride));
+    }
+
+    #[test]
+    fn tuning_database_round_trip() -> Result<(), String> {
+        use crate::clvecadd::tune;
+
+        let path = std::env::temp_dir().join("clvecadd_tuning_test.db");
+        let key = tune::TuningKey {
+            device: String::from("Test Device (1.0)"),
+            kernel: String::from("addVectors"),
+            options: String::from("-D ARRAY_TYPE=int"),
+            size_bucket: tune::get_size_bucket(1000),
+        };
+        let entry = tune::TuningEntry {
+            local: 128,
+            elements_per_item: 4,
+            nanos: 1234,
+        };
+
+        let mut db = tune::TuningDb::new(&path);
+        db.insert(key.clone(), entry);
+        match db.save() {
+            Ok(_) => (),
+            Err(error) => return Err(error),
+        };
+
+        let loaded = match tune::TuningDb::load(&path) {
+            Ok(loaded) => loaded,
+            Err(error) => return Err(error),
+        };
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded.get(&key), Some(entry));
+        assert_eq!(tune::get_size_bucket(1000), 10);
+        assert_eq!(tune::get_size_bucket(1024), 11);
+        Ok(())
+    }
+
+    #[test]
+    fn autotune_vecadd_kernel() -> Result<(), String> {
+        let devices: Vec<opencl3::device::Device>;
+        let ctx: opencl3::context::Context;
+        let queue: opencl3::command_queue::CommandQueue;
+        unsafe {
+            devices = crate::clvecadd::setup::get_all_gpus().unwrap_unchecked();
+            (ctx, queue) = crate::clvecadd::setup::get_context_for_device(devices, 0).unwrap_unchecked();
+        }
+
+        let size = 4096;
+        let mut a: Vec<f32> = vec![1.0; size];
+        let mut b: Vec<f32> = vec![2.0; size];
+        let mut c: Vec<f32> = vec![0.0; size];
+        let buffer_a = crate::clvecadd::buffer::create_buffer(&ctx, &mut a, crate::clvecadd::buffer::MemMode::Read).unwrap();
+        let buffer_b = crate::clvecadd::buffer::create_buffer(&ctx, &mut b, crate::clvecadd::buffer::MemMode::Read).unwrap();
+        let buffer_c = crate::clvecadd::buffer::create_buffer(&ctx, &mut c, crate::clvecadd::buffer::MemMode::Write).unwrap();
+        let kernel = match crate::prepare_kernel_for_vecadd(&ctx, &buffer_a, &buffer_b, &buffer_c, size) {
+            Ok(kernel) => kernel,
+            Err(error) => return Err(error),
+        };
+
+        match crate::clvecadd::tune::autotune(&queue, &kernel, size, &[1, 2, 4], 2) {
+            Ok(best) => {
+                assert!(best.local > 0);
+                assert!([1, 2, 4].contains(&best.elements_per_item));
+                return Ok(());
+            },
+            Err(error) => return Err(error),
+        };
+    }
+
+    #[test]
+    fn nd_range_rounds_each_dimension() {
+        use crate::clvecadd::exec::NdRange;
+
+        let range = NdRange::new(&[100, 30]).with_local(&[16, 8]).with_offset(&[4, 2]);
+        assert_eq!(range.validate(), Ok(()));
+        assert_eq!(range.dims(), 2);
+        assert_eq!(&range.get_rounded_global()[..2], &[112, 32]);
+        assert_eq!(range.offset(), &[4, 2]);
+
+        let unrounded = NdRange::new(&[7, 5, 3]);
+        assert_eq!(unrounded.get_rounded_global(), [7, 5, 3]);
+        assert_eq!(unrounded.local(), None);
+
+        assert!(NdRange::new(&[8, 8]).with_local(&[8]).validate().is_err());
+        assert!(NdRange::new(&[8]).with_offset(&[1, 1]).validate().is_err());
+        assert!(NdRange::new(&[]).validate().is_err());
+
+        let too_many = NdRange::new(&[8, 8, 8, 8]).with_local(&[2, 2, 2, 2]).with_offset(&[1, 1, 1, 1]);
+        assert!(too_many.validate().is_err());
+        assert_eq!(too_many.global(), &[8, 8, 8]);
+        assert_eq!(too_many.local(), Some(&[2, 2, 2][..]));
+        assert_eq!(too_many.offset(), &[1, 1, 1]);
+        assert_eq!(too_many.get_rounded_global(), [8, 8, 8]);
+    }
+
+    #[test]
+    fn report_derives_durations_and_bandwidth() {
+        use crate::clvecadd::profile::{Timing, VecAddReport};
+
+        let timing = Timing { queued: 100, submit: 150, start: 200, end: 1200 };
+        assert_eq!(timing.queue_delay(), 50);
+        assert_eq!(timing.submit_delay(), 50);
+        assert_eq!(timing.duration(), 1000);
+        assert_eq!(timing.latency(), 1100);
+
+        let report = VecAddReport {
+            uploads: vec![
+                Timing { queued: 0, submit: 0, start: 0, end: 1000 },
+                Timing { queued: 0, submit: 0, start: 500, end: 2000 },
+            ],
+            kernel: Timing { queued: 0, submit: 0, start: 2000, end: 2500 },
+            download: Timing { queued: 0, submit: 0, start: 2500, end: 3500 },
+            bytes_uploaded: 4000,
+            bytes_downloaded: 2000,
+            bytes_processed: 6000,
+        };
+        assert_eq!(report.upload_time(), 2000);
+        assert_eq!(report.total_time(), 3500);
+        assert_eq!(report.upload_bandwidth_gbs(), 2.0);
+        assert_eq!(report.download_bandwidth_gbs(), 2.0);
+        assert_eq!(report.kernel_bandwidth_gbs(), 12.0);
+    }
+
+    #[test]
+    fn perform_vecadd_with_report() -> Result<(), String> {
+        let devices: Vec<opencl3::device::Device>;
+        let ctx: opencl3::context::Context;
+        let queue: opencl3::command_queue::CommandQueue;
+        unsafe {
+            devices = crate::clvecadd::setup::get_all_gpus().unwrap_unchecked();
+            (ctx, queue) = crate::clvecadd::setup::get_context_for_device(devices, 0).unwrap_unchecked();
+        }
+
+        let mut a: Vec<i32> = vec![1, 2, 3, 4, 5];
+        let mut b: Vec<i32> = vec![6, 7, 8, 9, 10, 11];
+        let desired_outcome: Vec<i32> = vec![7, 9, 11, 13, 15];
+        match crate::vecadd_with_report(&ctx, &queue, &mut a, &mut b) {
+            Ok((c, report)) => {
+                assert_eq!(c, desired_outcome);
+                assert_eq!(report.uploads.len(), 2);
+                assert_eq!(report.bytes_downloaded, 20);
+                assert!(report.kernel.end >= report.kernel.start);
+                return Ok(());
+            },
+            Err(error) => return Err(error),
+        };
+    }
+
+    #[test]
+    fn trace_json_has_tracks_and_flows() {
+        let timing = |start: u64, end: u64| crate::clvecadd::profile::Timing {
+            queued: start,
+            submit: start,
+            start,
+            end,
+        };
+        let entries = vec![
+            crate::clvecadd::trace::TraceEntry {
+                id: 0,
+                track: 0,
+                name: String::from("write_buffer"),
+                kernel: None,
+                bytes: 64,
+                dependencies: vec![],
+                timing: timing(1000, 3000),
+            },
+            crate::clvecadd::trace::TraceEntry {
+                id: 1,
+                track: 1,
+                name: String::from("addVectors"),
+                kernel: Some(String::from("addVectors")),
+                bytes: 0,
+                dependencies: vec![0],
+                timing: timing(4000, 9000),
+            },
+        ];
+        let tracks = vec![String::from("upload"), String::from("compute")];
+
+        let content = crate::clvecadd::trace::get_trace_json(&entries, &tracks).unwrap();
+        let trace: serde_json::Value = serde_json::from_str(&content).unwrap();
+        let events = trace["traceEvents"].as_array().unwrap();
+        let phases: Vec<&str> = events.iter().map(|event| event["ph"].as_str().unwrap()).collect();
+        assert_eq!(phases, vec!["M", "M", "X", "X", "s", "f"]);
+        assert_eq!(events[1]["args"]["name"], "compute");
+        assert_eq!(events[3]["tid"], 1);
+        assert_eq!(events[3]["ts"], 3.0);
+        assert_eq!(events[3]["dur"], 5.0);
+        assert_eq!(events[3]["args"]["kernel"], "addVectors");
+        assert_eq!(events[2]["args"]["bytes"], 64);
+        assert_eq!(events[4]["ts"], 2.0);
+    }
+
+    #[test]
+    fn record_task_graph_trace() -> Result<(), String> {
+        let devices: Vec<opencl3::device::Device>;
+        let ctx: opencl3::context::Context;
+        let queue: opencl3::command_queue::CommandQueue;
+        unsafe {
+            devices = crate::clvecadd::setup::get_all_gpus().unwrap_unchecked();
+            (ctx, queue) = crate::clvecadd::setup::get_context_for_device(devices, 0).unwrap_unchecked();
+        }
+
+        let mut a: Vec<i32> = vec![1, 2, 3, 4, 5];
+        let mut b: Vec<i32> = vec![6, 7, 8, 9, 10];
+        let mut c: Vec<i32> = vec![0; 5];
+        let buffer_a = crate::clvecadd::buffer::create_buffer(&ctx, &mut a, crate::clvecadd::buffer::MemMode::Read).unwrap();
+        let buffer_b = crate::clvecadd::buffer::create_buffer(&ctx, &mut b, crate::clvecadd::buffer::MemMode::Read).unwrap();
+        let buffer_c = crate::clvecadd::buffer::create_buffer(&ctx, &mut c, crate::clvecadd::buffer::MemMode::Write).unwrap();
+        let kernel = match crate::prepare_kernel_for_vecadd(&ctx, &buffer_a, &buffer_b, &buffer_c, 5) {
+            Ok(kernel) => kernel,
+            Err(error) => return Err(error),
+        };
+
+        let mut recorder = crate::clvecadd::trace::TraceRecorder::new();
+        match recorder.name_queue(&queue, "gpu") {
+            Ok(_) => (),
+            Err(error) => return Err(error),
+        };
+
+        let mut graph = crate::clvecadd::graph::TaskGraph::new();
+        graph.write(&buffer_a, &a);
+        graph.write(&buffer_b, &b);
+        graph.launch(&kernel, 5, &[&buffer_a, &buffer_b], &[&buffer_c]);
+        graph.read(&buffer_c, &mut c);
+        match graph.submit_traced(&queue, &mut recorder) {
+            Ok(_) => (),
+            Err(error) => return Err(error),
+        };
+
+        let entries = match recorder.collect() {
+            Ok(entries) => entries,
+            Err(error) => return Err(error),
+        };
+        assert_eq!(entries.len(), 4);
+        assert_eq!(entries[2].kernel.as_deref(), Some("addVectors"));
+        assert_eq!(entries[2].dependencies, vec![0, 1]);
+        assert_eq!(entries[3].bytes, 20);
+        assert!(recorder.to_json().unwrap().contains("\"gpu\""));
+        assert_eq!(c, vec![7, 9, 11, 13, 15]);
+        Ok(())
+    }
+
+    #[test]
+    fn trace_records_shared_enqueue_paths() -> Result<(), String> {
+        let devices: Vec<opencl3::device::Device>;
+        let ctx: opencl3::context::Context;
+        let queue: opencl3::command_queue::CommandQueue;
+        unsafe {
+            devices = crate::clvecadd::setup::get_all_gpus().unwrap_unchecked();
+            (ctx, queue) = crate::clvecadd::setup::get_context_for_device(devices, 0).unwrap_unchecked();
+        }
+
+        let mut a: Vec<i32> = vec![1, 2, 3, 4, 5];
+        let mut b: Vec<i32> = vec![6, 7, 8, 9, 10];
+        crate::clvecadd::trace::start();
+        let added = crate::vecadd(&ctx, &queue, &mut a, &mut b);
+        let multiplied = crate::clvecadd::arith::binary_op(&ctx, &queue, crate::clvecadd::arith::BinaryOp::Mul, &a, &b);
+        let recorder = match crate::clvecadd::trace::stop() {
+            Some(recorder) => recorder,
+            None => return Err(String::from("trace stopped before it was collected")),
+        };
+        match (added, multiplied) {
+            (Ok(_), Ok(_)) => (),
+            (Err(error), _) | (_, Err(error)) => return Err(error),
+        };
+
+        // other tests may enqueue while the trace is active, only look for ours
+        let entries = match recorder.collect() {
+            Ok(entries) => entries,
+            Err(error) => return Err(error),
+        };
+        let add = match entries.iter().find(|entry| entry.kernel.as_deref() == Some("addVectors")) {
+            Some(add) => add,
+            None => return Err(String::from("vecadd kernel was not traced")),
+        };
+        assert_eq!(add.dependencies.len(), 2);
+        assert!(add.dependencies.iter().all(|id| entries[*id].name == "write_buffer"));
+        assert!(entries.iter().any(|entry| entry.kernel.as_deref() == Some("mulVectors")));
+        assert!(entries.iter().any(|entry| entry.name == "read_buffer" && entry.bytes == 20));
+        assert!(!crate::clvecadd::trace::is_active());
+        Ok(())
+    }
+
+    #[test]
+    fn parse_opencl_c_versions() {
+        use crate::clvecadd::options::ClStandard;
+        assert_eq!(ClStandard::parse("OpenCL C 1.2 "), Ok(ClStandard::CL1_2));
+        assert_eq!(ClStandard::parse("OpenCL C 3.0 (Build 0)"), Ok(ClStandard::CL3_0));
+        assert!(ClStandard::parse("OpenCL 3.0 CUDA").is_err());
+        assert!(ClStandard::CL1_2 < ClStandard::CL2_0);
+        assert_eq!(ClStandard::CL2_0.to_string(), "CL2.0");
+    }
+
+    #[test]
+    fn build_options_are_canonical() {
+        use crate::clvecadd::options::{BuildOptions, ClStandard, Warnings};
+        let first = BuildOptions::new()
+            .define("ARRAY_TYPE", "float")
+            .define("BLOCK", "4")
+            .fast_math(true)
+            .warnings(Warnings::AsErrors)
+            .standard(ClStandard::CL1_2)
+            .include_path(std::path::Path::new("src/opencl/include dir"));
+        let second = BuildOptions::new()
+            .standard(ClStandard::CL1_2)
+            .include_path(std::path::Path::new("src/opencl/include dir"))
+            .warnings(Warnings::AsErrors)
+            .define("BLOCK", "4")
+            .fast_math(true)
+            .define("ARRAY_TYPE", "float");
+
+        assert_eq!(
+            first.to_string(),
+            "-cl-std=CL1.2 -cl-fast-relaxed-math -Werror -I \"src/opencl/include dir\" -D ARRAY_TYPE=float -D BLOCK=4"
+        );
+        assert_eq!(first.to_string(), second.to_string());
+        assert_eq!(first.cache_key(), second.cache_key());
+        assert_ne!(first.cache_key(), second.define("ARRAY_TYPE", "int").cache_key());
+        assert_eq!(BuildOptions::new().optimize(false).flag("DEBUG").to_string(), "-cl-opt-disable -D DEBUG");
+    }
+
+    #[test]
+    fn parse_build_log_diagnostics() {
+        use crate::clvecadd::buildlog::{parse_build_log, Severity};
+        let log = "<kernel>:4:12: error: use of undeclared identifier 'gid'\n    c[gid] = a[gid] + b[gid];\n           ^\n<kernel>:2:5: warning: unused variable 'x'\nerror: Compiler frontend failed.\n";
+        let diagnostics = parse_build_log(log);
+
+        assert_eq!(diagnostics.len(), 3);
+        assert_eq!(diagnostics[0].file.as_deref(), Some("<kernel>"));
+        assert_eq!(diagnostics[0].line, Some(4));
+        assert_eq!(diagnostics[0].column, Some(12));
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert_eq!(diagnostics[0].message, "use of undeclared identifier 'gid'");
+        assert_eq!(diagnostics[1].severity, Severity::Warning);
+        assert_eq!(diagnostics[2].file, None);
+        assert_eq!(diagnostics[2].message, "Compiler frontend failed.");
+    }
+
+    #[test]
+    fn build_failure_carries_log() -> Result<(), String> {
+        let devices: Vec<opencl3::device::Device>;
+        let ctx: opencl3::context::Context;
+        unsafe {
+            devices = crate::clvecadd::setup::get_all_gpus().unwrap_unchecked();
+            (ctx, _) = crate::clvecadd::setup::get_context_for_device(devices, 0).unwrap_unchecked();
+        }
+
+        let source = "__kernel void broken(__global int* a) {\n    a[0] = missing;\n}\n";
+        match crate::clvecadd::buildlog::build_from_source(&ctx, "broken.cl", source, "") {
+            Ok(_) => return Err(String::from("broken source built")),
+            Err(failure) => {
+                assert_eq!(failure.logs.len(), 1);
+                assert!(failure.logs[0].log.contains("missing"));
+                assert!(failure.logs[0].errors().any(|error| error.line == Some(2)));
+                assert!(failure.to_string().starts_with("error building source file broken.cl"));
+            },
+        };
+        Ok(())
+    }
+
+    #[test]
+    fn embedded_headers_are_available() {
+        let headers = crate::clvecadd::link::HeaderMap::embedded();
+        assert!(headers.names().any(|name| name == "clvecadd.h"));
+        assert!(headers.get("clvecadd.h").unwrap().contains("FOR_EACH_GID"));
+        assert!(crate::clvecadd::link::HeaderMap::new().is_empty());
+    }
+
+    #[test]
+    fn compile_and_link_sources_with_headers() -> Result<(), String> {
+        let devices: Vec<opencl3::device::Device>;
+        let ctx: opencl3::context::Context;
+        let queue: opencl3::command_queue::CommandQueue;
+        unsafe {
+            devices = crate::clvecadd::setup::get_all_gpus().unwrap_unchecked();
+            (ctx, queue) = crate::clvecadd::setup::get_context_for_device(devices, 0).unwrap_unchecked();
+        }
+
+        let mut headers = crate::clvecadd::link::HeaderMap::embedded();
+        headers.insert("offset.h", "int add_offset(int value);\n");
+        let library = "int add_offset(int value) { return value + 40; }\n";
+        let kernels = "#include \"clvecadd.h\"\n#include \"offset.h\"\n__kernel void offsetVector(__global int* a, ulong num) {\n  FOR_EACH_GID(gid, num)\n    a[gid] = add_offset(a[gid]);\n}\n";
+
+        let prog = match crate::clvecadd::link::compile_and_link(
+            &ctx,
+            &[("offset.cl", library), ("kernels.cl", kernels)],
+            &headers,
+            "",
+            "",
+        ) {
+            Ok(prog) => prog,
+            Err(error) => return Err(error),
+        };
+        assert!(prog.kernel_names().contains("offsetVector"));
+
+        let mut a: Vec<i32> = vec![1, 2, 3];
+        let mut buffer_a = crate::clvecadd::buffer::create_buffer(&ctx, &mut a, crate::clvecadd::buffer::MemMode::ReadWrite).unwrap();
+        let kernel = match prog.create_kernel("offsetVector") {
+            Ok(kernel) => kernel,
+            Err(error) => return Err(error),
+        };
+        unsafe {
+            kernel.set_arg(0, &opencl3::memory::ClMem::get(&buffer_a)).unwrap();
+            kernel.set_arg(1, &(a.len() as u64)).unwrap();
+        }
+        let event = match crate::clvecadd::exec::execute_kernel(&queue, &kernel, a.len(), Vec::new()) {
+            Ok(event) => event,
+            Err(error) => return Err(error),
+        };
+        match crate::clvecadd::buffer::read_buffer(&queue, &mut buffer_a, &mut a, [event]) {
+            Ok(read_event) => read_event.wait().unwrap(),
+            Err(error) => return Err(error),
+        };
+        assert_eq!(a, vec![41, 42, 43]);
+        Ok(())
+    }
+
+    #[test]
+    fn link_failure_reports_unresolved_symbols() -> Result<(), String> {
+        let devices: Vec<opencl3::device::Device>;
+        let ctx: opencl3::context::Context;
+        unsafe {
+            devices = crate::clvecadd::setup::get_all_gpus().unwrap_unchecked();
+            (ctx, _) = crate::clvecadd::setup::get_context_for_device(devices, 0).unwrap_unchecked();
+        }
+
+        let kernels = "int missing(int value);\n__kernel void useMissing(__global int* a) { a[0] = missing(a[0]); }\n";
+        match crate::clvecadd::link::compile_and_link(&ctx, &[("kernels.cl", kernels)], &crate::clvecadd::link::HeaderMap::new(), "", "") {
+            Ok(_) => return Err(String::from("linking an unresolved symbol succeeded")),
+            Err(error) => assert!(error.contains("error linking programs"), "{}", error),
+        };
+        Ok(())
+    }
+
+    #[test]
+    fn detect_spirv_modules() {
+        let mut module: Vec<u8> = vec![0; 20];
+        module[..4].copy_from_slice(&0x07230203u32.to_le_bytes());
+        assert!(crate::clvecadd::il::is_spirv(&module));
+        module[..4].copy_from_slice(&0x07230203u32.to_be_bytes());
+        assert!(crate::clvecadd::il::is_spirv(&module));
+        assert!(!crate::clvecadd::il::is_spirv(&module[..16]));
+        assert!(!crate::clvecadd::il::is_spirv(b"__kernel void addVectors() {}"));
+    }
+
+    #[test]
+    fn outdated_il_modules_are_rejected() {
+        use crate::clvecadd::il::{check_module_source, get_source_hash_path};
+        let dir = std::env::temp_dir().join("clvecadd_il_hash_test");
+        std::fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("vecadd.cl");
+        let module = dir.join("vecadd_int.spv");
+        std::fs::write(&source, "__kernel void addVectors() {}").unwrap();
+        assert_eq!(get_source_hash_path(&module), dir.join("vecadd_int.spv.hash"));
+        assert!(check_module_source(&module, &source).is_err());
+
+        let hash = crate::clvecadd::options::get_stable_hash(b"__kernel void addVectors() {}");
+        std::fs::write(get_source_hash_path(&module), format!("{:016x}\n", hash)).unwrap();
+        assert_eq!(check_module_source(&module, &source), Ok(()));
+
+        std::fs::write(&source, "__kernel void subVectors() {}").unwrap();
+        let error = check_module_source(&module, &source).unwrap_err();
+        assert!(error.contains("rerun scripts/build_spirv.sh"), "{}", error);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn reject_missing_il_module() {
+        let devices: Vec<opencl3::device::Device>;
+        let ctx: opencl3::context::Context;
+        unsafe {
+            devices = crate::clvecadd::setup::get_all_gpus().unwrap_unchecked();
+            (ctx, _) = crate::clvecadd::setup::get_context_for_device(devices, 0).unwrap_unchecked();
+        }
+
+        let missing = [std::path::Path::new("src/bin/vecadd/spirv/missing.spv")];
+        assert!(crate::clvecadd::il::create_and_build_from_il(&ctx, &missing, "").is_err());
+    }
+
+    #[test]
+    fn load_spirv_module() -> Result<(), String> {
+        use opencl3::memory::ClMem;
+
+        let devices: Vec<opencl3::device::Device>;
+        let ctx: opencl3::context::Context;
+        let queue: opencl3::command_queue::CommandQueue;
+        unsafe {
+            devices = crate::clvecadd::setup::get_all_gpus().unwrap_unchecked();
+            (ctx, queue) = crate::clvecadd::setup::get_context_for_device(devices, 0).unwrap_unchecked();
+        }
+
+        // devices before OpenCL 2.1 take the source path instead
+        if !crate::clvecadd::il::supports_spirv(&ctx) {
+            return Ok(());
+        }
+
+        let module = [std::path::Path::new("src/test/spirv/add_int.spv")];
+        let progs = match crate::clvecadd::il::create_and_build_from_il(&ctx, &module, "") {
+            Ok(progs) => progs,
+            Err(error) => return Err(error),
+        };
+        let kernel = match opencl3::kernel::Kernel::create(&progs[0], "addVectors") {
+            Ok(kernel) => kernel,
+            Err(error) => return Err(format!("not able to get kernel addVectors: {}", error)),
+        };
+
+        let mut a: Vec<i32> = vec![1, 2, 3, 4, 5];
+        let mut b: Vec<i32> = vec![6, 7, 8, 9, 10];
+        let mut c: Vec<i32> = vec![0; 5];
+        let mut buffers = Vec::new();
+        for (input, mode) in [
+            (&mut a, crate::clvecadd::buffer::MemMode::Read),
+            (&mut b, crate::clvecadd::buffer::MemMode::Read),
+            (&mut c, crate::clvecadd::buffer::MemMode::Write),
+        ] {
+            match crate::clvecadd::buffer::create_buffer(&ctx, input, mode) {
+                Ok(buffer) => buffers.push(buffer),
+                Err(error) => return Err(error),
+            };
+        }
+
+        for (index, buffer) in buffers.iter().enumerate() {
+            match unsafe { kernel.set_arg(index as u32, &buffer.get()) } {
+                Ok(_) => (),
+                Err(error) => return Err(format!("error setting kernel argument {}: {}", index + 1, error)),
+            };
+        }
+        match unsafe { kernel.set_arg(3, &5u64) } {
+            Ok(_) => (),
+            Err(error) => return Err(format!("error setting kernel argument 4: {}", error)),
+        };
+
+        // the module has no grid-stride loop, so tuned launch configurations do not apply
+        let local_size = crate::clvecadd::exec::LocalSize::Driver;
+        let run = match crate::clvecadd::exec::execute_kernel_with_local_size(&queue, &kernel, 5, local_size, []) {
+            Ok(run) => run,
+            Err(error) => return Err(error),
+        };
+        let read = match crate::clvecadd::buffer::read_buffer(&queue, &mut buffers[2], &mut c, [run]) {
+            Ok(read) => read,
+            Err(error) => return Err(error),
+        };
+        match read.wait() {
+            Ok(_) => (),
+            Err(error) => return Err(format!("error waiting for read: {}", error)),
+        };
+
+        assert_eq!(c, vec![7, 9, 11, 13, 15]);
+        Ok(())
+    }
+
+    #[test]
+    fn binary_container_round_trip() {
+        use crate::clvecadd::container::{decode, encode, BinaryHeader};
+        let header = BinaryHeader {
+            device: String::from("NVIDIA GeForce RTX 3080"),
+            driver: String::from("535.54"),
+            options_hash: 0x1234,
+            source_hash: 0xabcd,
+        };
+        let binary: Vec<u8> = vec![0, 10, 10, 255, 7];
+
+        let content = encode(&header, &binary);
+        let (decoded, decoded_binary) = decode(&content).unwrap();
+        assert_eq!(decoded, header);
+        assert_eq!(decoded_binary, &binary[..]);
+        assert!(decode(&content[..content.len() - 1]).unwrap_err().contains("truncated"));
+        assert!(decode(&binary).is_err());
+    }
+
+    #[test]
+    fn binary_header_mismatch_is_reported() {
+        let header = crate::clvecadd::container::BinaryHeader {
+            device: String::from("gfx1030"),
+            driver: String::from("3513.0"),
+            options_hash: 1,
+            source_hash: 2,
+        };
+
+        let mut other_device = header.clone();
+        other_device.device = String::from("gfx90a");
+        let mut other_source = header.clone();
+        other_source.source_hash = 3;
+
+        assert_eq!(header.check(&header), Ok(()));
+        assert!(header.check(&other_device).unwrap_err().contains("built for device gfx1030, not for gfx90a"));
+        assert!(header.check(&other_source).unwrap_err().contains("out of date"));
+    }
+
+    #[test]
+    fn binary_names_depend_on_device_and_options() {
+        use crate::clvecadd::container::get_binary_file_name;
+        let types = crate::clvecadd::traits::get_opencl_num_types();
+        assert_eq!(types.len(), 11);
+        assert!(types.contains(&"double"));
+
+        let name = get_binary_file_name("vecadd", "gfx1030", "3513.0", "-D ARRAY_TYPE=int");
+        assert!(name.starts_with("vecadd-") && name.ends_with(".bin"));
+        assert_eq!(name, get_binary_file_name("vecadd", "gfx1030", "3513.0", " -D ARRAY_TYPE=int "));
+        assert_ne!(name, get_binary_file_name("vecadd", "gfx90a", "3513.0", "-D ARRAY_TYPE=int"));
+        assert_ne!(name, get_binary_file_name("vecadd", "gfx1030", "3513.0", "-D ARRAY_TYPE=uint"));
+    }
+
+    #[test]
+    fn unsupported_type_error_is_recognisable() {
+        use crate::clvecadd::traits::OpenclNum;
+        assert_eq!(<half::f16>::required_extension(), Some("cl_khr_fp16"));
+        assert_eq!(<f64>::required_extension(), Some("cl_khr_fp64"));
+        assert_eq!(<i32>::required_extension(), None);
+        assert_eq!(crate::clvecadd::traits::get_required_extension("double"), Some("cl_khr_fp64"));
+
+        let error = crate::clvecadd::caps::TypeSupportError::Unsupported(crate::clvecadd::caps::UnsupportedType {
+            element_type: String::from("double"),
+            device: String::from("Mali-G78"),
+            extension: String::from("cl_khr_fp64"),
+        });
+        let message: String = error.clone().into();
+        assert!(message.starts_with(crate::clvecadd::caps::UNSUPPORTED_TYPE));
+        assert!(message.contains("double requires cl_khr_fp64"));
+        match error {
+            crate::clvecadd::caps::TypeSupportError::Unsupported(unsupported) => assert_eq!(unsupported.device, "Mali-G78"),
+            crate::clvecadd::caps::TypeSupportError::Query(error) => panic!("{}", error),
+        };
+    }
+
+    #[test]
+    fn perform_double_vecadd_with_cpu_fallback() -> Result<(), String> {
+        let devices: Vec<opencl3::device::Device>;
+        let ctx: opencl3::context::Context;
+        let queue: opencl3::command_queue::CommandQueue;
+        unsafe {
+            devices = crate::clvecadd::setup::get_all_gpus().unwrap_unchecked();
+            (ctx, queue) = crate::clvecadd::setup::get_context_for_device(devices, 0).unwrap_unchecked();
+        }
+
+        let mut a: Vec<f64> = vec![0.5, 1.5, 2.5];
+        let mut b: Vec<f64> = vec![1.0, 2.0, 3.0];
+        match crate::vecadd_with_cpu_fallback(&ctx, &queue, &mut a, &mut b) {
+            Ok(c) => {
+                assert_eq!(c, vec![1.5, 3.5, 5.5]);
+                return Ok(());
+            },
+            Err(error) => return Err(error),
+        };
+    }
+
+    #[test]
+    fn kernel_handles_describe_their_kernels() {
+        use crate::clvecadd::registry::{ArgKind, Kernels, NUMERIC_TYPES};
+        assert_eq!(NUMERIC_TYPES.to_vec(), crate::clvecadd::traits::get_opencl_num_types());
+        assert_eq!(Kernels::AddVectors.entry_point(), "addVectors");
+        assert_eq!(Kernels::SubVectorsInplace.entry_point(), "subVectorsInplace");
+        assert_eq!(Kernels::AddVectorsInplace.info().args, &[ArgKind::InOut, ArgKind::Input, ArgKind::Count]);
+        assert_eq!(Kernels::SubVectors.info().source.path, "src/opencl/vecadd/vecadd.cl");
+        assert!(Kernels::ALL.iter().all(|handle| handle.supports("int") || handle.supports("float")));
+        assert!(!Kernels::PowVectors.supports("int"));
+        assert!(Kernels::AddVectors.supports("float"));
+        assert!(!Kernels::AddVectorsSat.supports("float"));
+        assert!(!Kernels::AddVectors.supports("bool"));
+    }
+
+    #[test]
+    fn registry_builds_each_type_once() -> Result<(), String> {
+        let devices: Vec<opencl3::device::Device>;
+        let ctx: opencl3::context::Context;
+        unsafe {
+            devices = crate::clvecadd::setup::get_all_gpus().unwrap_unchecked();
+            (ctx, _) = crate::clvecadd::setup::get_context_for_device(devices, 0).unwrap_unchecked();
+        }
+
+        let registry = crate::clvecadd::registry::KernelRegistry::new();
+        for handle in crate::clvecadd::registry::Kernels::ALL.into_iter().filter(|handle| handle.supports("int")) {
+            let kernel = match registry.get::<i32>(&ctx, handle) {
+                Ok(kernel) => kernel,
+                Err(error) => return Err(error),
+            };
+            assert_eq!(kernel.function_name().unwrap(), handle.entry_point());
+        }
+        assert_eq!(registry.len(), 1);
+
+        match registry.get::<f32>(&ctx, crate::clvecadd::registry::Kernels::AddVectors) {
+            Ok(_) => (),
+            Err(error) => return Err(error),
+        };
+        assert_eq!(registry.len(), 2);
+
+        assert_eq!(registry.remove_context(&ctx), 2);
+        assert!(registry.is_empty());
+        assert_eq!(registry.remove_context(&ctx), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn ahead_of_time_sources_are_accounted_for() {
+        use crate::clvecadd::aot::{EXCLUDED_SOURCES, KERNEL_SOURCES};
+        for source in &KERNEL_SOURCES {
+            assert!(std::path::Path::new(source.path).is_file());
+            assert!(EXCLUDED_SOURCES.iter().all(|excluded| excluded.name != source.name));
+        }
+        assert!(EXCLUDED_SOURCES.iter().any(|excluded| excluded.path == "src/opencl/image/imageops.cl"));
+    }
+
+    #[test]
+    fn find_registered_kernels_by_name() {
+        use crate::clvecadd::registry::{find_kernel, Kernels};
+        assert_eq!(find_kernel("vecadd.cl", "subVectors"), Some(Kernels::SubVectors));
+        assert_eq!(find_kernel("vecadd.cl", "addVector"), None);
+        assert_eq!(find_kernel("imageops.cl", "addVectors"), None);
+    }
+
+    #[test]
+    fn launcher_arguments_are_checked_against_kernels() {
+        use crate::clvecadd::launch::{check_args, IntoKernelArg};
+        use crate::clvecadd::registry::{ArgKind, Kernels};
+        assert_eq!(<&[f32] as IntoKernelArg<'_>>::kind(), ArgKind::Input);
+        assert_eq!(<&mut [f32] as IntoKernelArg<'_>>::kind(), ArgKind::InOut);
+        assert_eq!(<u64 as IntoKernelArg<'_>>::kind(), ArgKind::Scalar);
+
+        assert!(check_args(Kernels::SubVectors, &[ArgKind::Input, ArgKind::Input, ArgKind::InOut, ArgKind::Scalar]).is_ok());
+        assert!(check_args(Kernels::SubVectors, &[ArgKind::Input, ArgKind::InOut, ArgKind::InOut, ArgKind::Scalar]).is_err());
+        assert!(check_args(Kernels::SubVectors, &[ArgKind::Input, ArgKind::Input, ArgKind::InOut]).is_err());
+        assert!(check_args(Kernels::Axpy, &[ArgKind::Scalar, ArgKind::Input, ArgKind::InOut, ArgKind::Scalar]).is_ok());
+
+        use crate::clvecadd::launch::check_lengths;
+        assert!(check_lengths(Kernels::SubVectors, &[Some(4), Some(4), Some(4), None], &[None, None, None, Some(4)]).is_ok());
+        assert!(check_lengths(Kernels::SubVectors, &[Some(4), Some(4), Some(4), None], &[None, None, None, Some(1000)]).is_err());
+        assert!(check_lengths(Kernels::SubVectors, &[Some(4), Some(2), Some(4), None], &[None, None, None, Some(2)]).is_err());
+        assert!(check_lengths(Kernels::SubVectors, &[Some(4), Some(4), Some(4), None], &[None, None, None, None]).is_err());
+        let checked = [Some(3), Some(3), Some(3), Some(1), None];
+        assert!(check_lengths(Kernels::AddVectorsChecked, &checked, &[None, None, None, None, Some(3)]).is_ok());
+    }
+
+    #[test]
+    fn perform_generated_kernel_launchers() -> Result<(), String> {
+        let devices: Vec<opencl3::device::Device>;
+        let ctx: opencl3::context::Context;
+        let queue: opencl3::command_queue::CommandQueue;
+        unsafe {
+            devices = crate::clvecadd::setup::get_all_gpus().unwrap_unchecked();
+            (ctx, queue) = crate::clvecadd::setup::get_context_for_device(devices, 0).unwrap_unchecked();
+        }
+
+        let a: Vec<i32> = vec![10, 20, 30, 40];
+        let b: Vec<i32> = vec![1, 2, 3, 4];
+        let mut c: Vec<i32> = vec![0; 4];
+        match crate::sub_vectors(&ctx, &queue, &a, &b, &mut c, 4) {
+            Ok(_) => assert_eq!(c, vec![9, 18, 27, 36]),
+            Err(error) => return Err(error),
+        };
+
+        let mut d: Vec<f32> = vec![0.5, 1.5];
+        match crate::add_vectors_inplace(&ctx, &queue, &mut d, &[1.0, 1.0], 2) {
+            Ok(_) => assert_eq!(d, vec![1.5, 2.5]),
+            Err(error) => return Err(error),
+        };
+
+        // the device buffers are sized to the slices, so neither may be overrun
+        assert!(crate::sub_vectors(&ctx, &queue, &a, &b, &mut c, 1000).is_err());
+        assert!(crate::sub_vectors(&ctx, &queue, &a[..4], &b[..2], &mut c, 2).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn generate_elementwise_sources() {
+        use crate::clvecadd::elementwise::ElementwiseOp;
+        let fma = ElementwiseOp::new("fma", 3, "a * b + c");
+        let source = fma.generate_source().unwrap();
+        assert!(source.contains("__kernel void fma(__global const ARRAY_TYPE *in_a, __global const ARRAY_TYPE *in_b, __global const ARRAY_TYPE *in_c, __global ARRAY_TYPE *out, ulong num)"));
+        assert!(source.contains("out[gid] = (ARRAY_TYPE)(a * b + c);"));
+
+        let scale = ElementwiseOp::new("twice", 1, "a + a").inplace(true);
+        let source = scale.generate_source().unwrap();
+        assert!(source.contains("__kernel void twice(__global ARRAY_TYPE *in_a, ulong num)"));
+        assert!(source.contains("in_a[gid] = (ARRAY_TYPE)(a + a);"));
+        assert_ne!(fma.get_stem().unwrap(), ElementwiseOp::new("fma", 3, "a * b - c").get_stem().unwrap());
+
+        assert!(ElementwiseOp::new("1st", 1, "a").validate().is_err());
+        assert!(ElementwiseOp::new("none", 0, "a").validate().is_err());
+        assert!(ElementwiseOp::new("quad", 4, "a").validate().is_err());
+        assert!(ElementwiseOp::new("evil", 1, "a; }").validate().is_err());
+    }
+
+    #[test]
+    fn perform_generated_elementwise_ops() -> Result<(), String> {
+        use crate::clvecadd::elementwise::{execute, ElementwiseOp};
+        let devices: Vec<opencl3::device::Device>;
+        let ctx: opencl3::context::Context;
+        let queue: opencl3::command_queue::CommandQueue;
+        unsafe {
+            devices = crate::clvecadd::setup::get_all_gpus().unwrap_unchecked();
+            (ctx, queue) = crate::clvecadd::setup::get_context_for_device(devices, 0).unwrap_unchecked();
+        }
+
+        let fma = ElementwiseOp::new("fma", 3, "a * b + c");
+        let mut out: Vec<i32> = vec![0; 3];
+        match execute(&ctx, &queue, &fma, &[&[1, 2, 3], &[4, 5, 6], &[1, 1, 1]], &mut out) {
+            Ok(_) => assert_eq!(out, vec![5, 11, 19]),
+            Err(error) => return Err(error),
+        };
+
+        let twice = ElementwiseOp::new("twice", 1, "a + a").inplace(true);
+        let mut values: Vec<f32> = vec![0.5, 1.5];
+        match execute(&ctx, &queue, &twice, &[], &mut values) {
+            Ok(_) => assert_eq!(values, vec![1.0, 3.0]),
+            Err(error) => return Err(error),
+        };
+
+        assert!(execute(&ctx, &queue, &fma, &[&[1, 2, 3]], &mut out).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn cpu_arithmetic_has_defined_integer_semantics() {
+        use crate::clvecadd::arith::{binary_op_cpu, unary_op_cpu, BinaryOp, UnaryOp};
+        assert_eq!(binary_op_cpu(BinaryOp::Div, &[7i32, -7, i32::MIN, 5], &[2, 0, -1, -2]).unwrap(), vec![3, 0, i32::MIN, -2]);
+        assert_eq!(binary_op_cpu(BinaryOp::Rem, &[7i32, -7, i32::MIN, 5], &[2, 0, -1, -2]).unwrap(), vec![1, -7, 0, 1]);
+        assert_eq!(binary_op_cpu(BinaryOp::Div, &[7u8, 255], &[0, 255]).unwrap(), vec![0, 1]);
+        assert_eq!(binary_op_cpu(BinaryOp::Mul, &[100i8, -3], &[3, 5]).unwrap(), vec![44, -15]);
+        assert_eq!(unary_op_cpu(UnaryOp::Abs, &[i16::MIN, -3, 4]).unwrap(), vec![i16::MIN, 3, 4]);
+        assert_eq!(unary_op_cpu(UnaryOp::Neg, &[1u32, 0]).unwrap(), vec![u32::MAX, 0]);
+        assert_eq!(binary_op_cpu(BinaryOp::Rem, &[5.5f32, -5.5], &[2.0, 2.0]).unwrap(), vec![1.5, -1.5]);
+        assert_eq!(binary_op_cpu(BinaryOp::Max, &[f64::NAN, 1.0], &[2.0, f64::NAN]).unwrap(), vec![2.0, 1.0]);
+    }
+
+    #[test]
+    fn gpu_arithmetic_matches_cpu() -> Result<(), String> {
+        use crate::clvecadd::arith::{binary_op, binary_op_cpu, unary_op, unary_op_cpu, BinaryOp, UnaryOp};
+        let devices: Vec<opencl3::device::Device>;
+        let ctx: opencl3::context::Context;
+        let queue: opencl3::command_queue::CommandQueue;
+        unsafe {
+            devices = crate::clvecadd::setup::get_all_gpus().unwrap_unchecked();
+            (ctx, queue) = crate::clvecadd::setup::get_context_for_device(devices, 0).unwrap_unchecked();
+        }
+
+        let a: Vec<i32> = vec![7, -7, i32::MIN, i32::MAX, 0, -1, 12345];
+        let b: Vec<i32> = vec![2, 0, -1, 2, 0, i32::MIN, -17];
+        for op in BinaryOp::ALL {
+            match binary_op(&ctx, &queue, op, &a, &b) {
+                Ok(c) => assert_eq!(c, binary_op_cpu(op, &a, &b).unwrap(), "{:?}", op),
+                Err(error) => return Err(error),
+            };
+        }
+
+        let a: Vec<u8> = vec![0, 1, 200, 255];
+        let b: Vec<u8> = vec![0, 3, 100, 255];
+        for op in BinaryOp::ALL {
+            match binary_op(&ctx, &queue, op, &a, &b) {
+                Ok(c) => assert_eq!(c, binary_op_cpu(op, &a, &b).unwrap(), "{:?}", op),
+                Err(error) => return Err(error),
+            };
+        }
+
+        let a: Vec<i64> = vec![i64::MIN, -5, 0, 5];
+        for op in UnaryOp::ALL {
+            match unary_op(&ctx, &queue, op, &a) {
+                Ok(c) => assert_eq!(c, unary_op_cpu(op, &a).unwrap(), "{:?}", op),
+                Err(error) => return Err(error),
+            };
+        }
+
+        let a: Vec<f32> = vec![5.5, -5.5, 1.0, -0.25];
+        let b: Vec<f32> = vec![2.0, 2.0, -4.0, 0.5];
+        for op in [BinaryOp::Mul, BinaryOp::Rem, BinaryOp::Min, BinaryOp::Max] {
+            match binary_op(&ctx, &queue, op, &a, &b) {
+                Ok(c) => assert_eq!(c, binary_op_cpu(op, &a, &b).unwrap(), "{:?}", op),
+                Err(error) => return Err(error),
+            };
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn cpu_scalar_vector_operations() {
+        use crate::clvecadd::arith::{add_scalar_cpu, axpby_cpu, axpy_cpu, scale_cpu};
+        assert_eq!(scale_cpu(3i32, &[1, -2, 3]).unwrap(), vec![3, -6, 9]);
+        assert_eq!(add_scalar_cpu(&[250u8, 1], 10).unwrap(), vec![4, 11]);
+
+        let mut y: Vec<f64> = vec![1.0, 2.0, 3.0];
+        axpy_cpu(2.0, &[1.0, 1.0], &mut y).unwrap();
+        assert_eq!(y, vec![3.0, 4.0, 3.0]);
+        axpby_cpu(1.0, &[1.0, 1.0, 1.0], 0.5, &mut y).unwrap();
+        assert_eq!(y, vec![2.5, 3.0, 2.5]);
+    }
+
+    #[test]
+    fn perform_scalar_vector_operations() -> Result<(), String> {
+        use crate::clvecadd::arith::{add_scalar, axpby, axpby_cpu, axpy, axpy_cpu, scale};
+        let devices: Vec<opencl3::device::Device>;
+        let ctx: opencl3::context::Context;
+        let queue: opencl3::command_queue::CommandQueue;
+        unsafe {
+            devices = crate::clvecadd::setup::get_all_gpus().unwrap_unchecked();
+            (ctx, queue) = crate::clvecadd::setup::get_context_for_device(devices, 0).unwrap_unchecked();
+        }
+
+        match scale(&ctx, &queue, 0.5f32, &[2.0, -4.0, 1.0]) {
+            Ok(c) => assert_eq!(c, vec![1.0, -2.0, 0.5]),
+            Err(error) => return Err(error),
+        };
+
+        match add_scalar(&ctx, &queue, &[i32::MAX, 1], 1) {
+            Ok(c) => assert_eq!(c, vec![i32::MIN, 2]),
+            Err(error) => return Err(error),
+        };
+
+        let x: Vec<i64> = vec![1, -2, 3, i64::MAX];
+        let mut y: Vec<i64> = vec![10, 20, 30, 2];
+        let mut expected = y.clone();
+        axpy_cpu(3, &x, &mut expected).unwrap();
+        match axpy(&ctx, &queue, 3, &x, &mut y) {
+            Ok(_) => assert_eq!(y, expected),
+            Err(error) => return Err(error),
+        };
+
+        axpby_cpu(-1, &x, 2, &mut expected).unwrap();
+        match axpby(&ctx, &queue, -1, &x, 2, &mut y) {
+            Ok(_) => assert_eq!(y, expected),
+            Err(error) => return Err(error),
+        };
+        Ok(())
+    }
+
+    #[test]
+    fn cpu_overflow_modes() {
+        use crate::clvecadd::arith::{is_integer_overflow, overflow_op_cpu, BinaryOp, Overflow};
+        assert_eq!(overflow_op_cpu(Overflow::Wrapping, BinaryOp::Add, &[120i8, -120], &[10, -10]).unwrap(), vec![-126, 126]);
+        assert_eq!(overflow_op_cpu(Overflow::Saturating, BinaryOp::Add, &[120i8, -120], &[10, -10]).unwrap(), vec![127, -128]);
+        assert_eq!(overflow_op_cpu(Overflow::Saturating, BinaryOp::Sub, &[3u16, 10], &[5, 5]).unwrap(), vec![0, 5]);
+        assert_eq!(overflow_op_cpu(Overflow::Checked, BinaryOp::Sub, &[3u16, 10], &[1, 5]).unwrap(), vec![2, 5]);
+        match overflow_op_cpu(Overflow::Checked, BinaryOp::Add, &[1u8, 200], &[1, 100]) {
+            Ok(_) => panic!("overflow not detected"),
+            Err(error) => assert!(is_integer_overflow(&error), "{}", error),
+        };
+        assert!(overflow_op_cpu(Overflow::Saturating, BinaryOp::Mul, &[1i32], &[1]).is_err());
+        assert_eq!(crate::vecadd_cpu(&vec![255u8], &vec![1]).unwrap(), vec![0]);
+    }
+
+    #[test]
+    fn gpu_overflow_modes_match_cpu() -> Result<(), String> {
+        use crate::clvecadd::arith::{is_integer_overflow, overflow_op, overflow_op_cpu, BinaryOp, Overflow};
+        let devices: Vec<opencl3::device::Device>;
+        let ctx: opencl3::context::Context;
+        let queue: opencl3::command_queue::CommandQueue;
+        unsafe {
+            devices = crate::clvecadd::setup::get_all_gpus().unwrap_unchecked();
+            (ctx, queue) = crate::clvecadd::setup::get_context_for_device(devices, 0).unwrap_unchecked();
+        }
+
+        let a: Vec<i8> = vec![120, -120, 0, i8::MIN, i8::MAX];
+        let b: Vec<i8> = vec![10, -10, 1, 1, -1];
+        for mode in [Overflow::Wrapping, Overflow::Saturating] {
+            for op in [BinaryOp::Add, BinaryOp::Sub] {
+                match overflow_op(&ctx, &queue, mode, op, &a, &b) {
+                    Ok(c) => assert_eq!(c, overflow_op_cpu(mode, op, &a, &b).unwrap(), "{:?} {:?}", mode, op),
+                    Err(error) => return Err(error),
+                };
+            }
+        }
+
+        match overflow_op(&ctx, &queue, Overflow::Checked, BinaryOp::Add, &[1u32, 2], &[3u32, 4]) {
+            Ok(c) => assert_eq!(c, vec![4, 6]),
+            Err(error) => return Err(error),
+        };
+        match overflow_op(&ctx, &queue, Overflow::Checked, BinaryOp::Sub, &[1u32, 2], &[3u32, 1]) {
+            Ok(_) => panic!("overflow not detected"),
+            Err(error) => assert!(is_integer_overflow(&error), "{}", error),
+        };
+
+        assert!(overflow_op(&ctx, &queue, Overflow::Saturating, BinaryOp::Add, &[1.0f32], &[1.0f32]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn ulp_distance_and_math_reference() {
+        use crate::clvecadd::math::{compare_ulp, math_op_cpu, MathFn, MathNum};
+        assert_eq!(<f32>::ulp_distance(1.0, 1.0 + f32::EPSILON), 1);
+        assert_eq!(<f32>::ulp_distance(-0.0, 0.0), 0);
+        assert_eq!(<f32>::ulp_distance(-f32::MIN_POSITIVE, f32::MIN_POSITIVE), 2 * 0x0080_0000);
+        assert_eq!(<f64>::ulp_distance(f64::NAN, 1.0), u64::MAX);
+        assert_eq!(<half::f16>::ulp_distance(half::f16::ONE, half::f16::from_bits(0x3c02)), 2);
+
+        assert_eq!(math_op_cpu(MathFn::Sqrt, &[&[4.0f64, 2.25]]).unwrap(), vec![2.0, 1.5]);
+        assert_eq!(math_op_cpu(MathFn::Pow, &[&[2.0f32, 9.0], &[10.0, 0.5]]).unwrap(), vec![1024.0, 3.0]);
+        assert!(math_op_cpu(MathFn::Pow, &[&[2.0f32]]).is_err());
+
+        let report = compare_ulp(&[1.0f32, 2.0, 3.0], &[1.0, 2.0 + 2.0 * f32::EPSILON, 3.0]).unwrap();
+        assert_eq!((report.max_ulp, report.index, report.elements), (1, 1, 3));
+
+        assert_eq!(MathFn::Sqrt.max_ulp::<f32>(), 3);
+        assert_eq!(MathFn::Sqrt.max_ulp::<f64>(), 0);
+        assert_eq!(MathFn::Pow.max_ulp::<half::f16>(), 4);
+        assert_eq!(MathFn::Sqrt.max_ulp::<half::f16>(), 2);
+    }
+
+    #[test]
+    fn gpu_math_functions_within_ulp_bounds() -> Result<(), String> {
+        use crate::clvecadd::math::{verify_math_op, MathFn};
+        let devices: Vec<opencl3::device::Device>;
+        let ctx: opencl3::context::Context;
+        let queue: opencl3::command_queue::CommandQueue;
+        unsafe {
+            devices = crate::clvecadd::setup::get_all_gpus().unwrap_unchecked();
+            (ctx, queue) = crate::clvecadd::setup::get_context_for_device(devices, 0).unwrap_unchecked();
+        }
+
+        let a: Vec<f32> = (1..=64).map(|i| i as f32 * 0.37).collect();
+        let b: Vec<f32> = (1..=64).map(|i| (i % 7) as f32 * 0.5 - 1.0).collect();
+        for f in MathFn::ALL {
+            let args: Vec<&[f32]> = if f.arity() == 2 { vec![&a, &b] } else { vec![&a] };
+            match verify_math_op(&ctx, &queue, f, &args, f.max_ulp::<f32>()) {
+                Ok(report) => assert_eq!(report.elements, 64),
+                Err(error) => return Err(error),
+            };
+        }
+
+        assert!(crate::clvecadd::math::math_op(&ctx, &queue, MathFn::Sqrt, &[&[4i32][..]]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn cpu_masks_compare_and_select() {
+        use crate::clvecadd::mask::{compare_cpu, filter, from_bools, select_cpu, to_bools, Comparison};
+        let a = [1.0f32, 2.0, f32::NAN, -0.0];
+        let b = [2.0f32, 2.0, 1.0, 0.0];
+        assert_eq!(compare_cpu(Comparison::Eq, &a, &b).unwrap(), vec![0, 1, 0, 1]);
+        assert_eq!(compare_cpu(Comparison::Lt, &a, &b).unwrap(), vec![1, 0, 0, 0]);
+        assert_eq!(compare_cpu(Comparison::Ge, &a, &b).unwrap(), vec![0, 1, 0, 1]);
+
+        let mask = compare_cpu(Comparison::Gt, &[3i32, 1, 5], &[2, 2, 2]).unwrap();
+        assert_eq!(to_bools(&mask), vec![true, false, true]);
+        assert_eq!(from_bools(&[true, false, true]), mask);
+        assert_eq!(select_cpu(&mask, &[10, 20, 30], &[-1, -2, -3]).unwrap(), vec![10, -2, 30]);
+        assert_eq!(filter(&mask, &[3, 1, 5]), vec![3, 5]);
+    }
+
+    #[test]
+    fn perform_mask_kernels() -> Result<(), String> {
+        use crate::clvecadd::mask::{compare, compare_cpu, select, Comparison};
+        let devices: Vec<opencl3::device::Device>;
+        let ctx: opencl3::context::Context;
+        let queue: opencl3::command_queue::CommandQueue;
+        unsafe {
+            devices = crate::clvecadd::setup::get_all_gpus().unwrap_unchecked();
+            (ctx, queue) = crate::clvecadd::setup::get_context_for_device(devices, 0).unwrap_unchecked();
+        }
+
+        let a: Vec<i16> = vec![-3, 0, 7, 7, i16::MIN];
+        let b: Vec<i16> = vec![0, 0, 5, 8, i16::MAX];
+        for cmp in Comparison::ALL {
+            match compare(&ctx, &queue, cmp, &a, &b) {
+                Ok(mask) => assert_eq!(mask, compare_cpu(cmp, &a, &b).unwrap(), "{:?}", cmp),
+                Err(error) => return Err(error),
+            };
+        }
+
+        let x: Vec<f32> = vec![1.5, f32::NAN, -2.0];
+        let y: Vec<f32> = vec![1.0, 1.0, -2.0];
+        let mask = match compare(&ctx, &queue, Comparison::Ge, &x, &y) {
+            Ok(mask) => mask,
+            Err(error) => return Err(error),
+        };
+        assert_eq!(mask, vec![1, 0, 1]);
+
+        match select(&ctx, &queue, &mask, &[10.0f32, 20.0, 30.0], &[0.0, 0.0, 0.0]) {
+            Ok(c) => assert_eq!(c, vec![10.0, 0.0, 30.0]),
+            Err(error) => return Err(error),
+        };
+        Ok(())
+    }
 }